@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use seriesdb::consts::DEFAULT_TABLE_GROUP;
 use seriesdb::prelude::*;
+use seriesdb::utils::{build_inner_key, build_tail_anchor};
 
 fn bench_db(c: &mut Criterion) {
   let db = NormalDb::open("./data/seriesdb_bench", &mut Options::new()).unwrap();
@@ -16,7 +18,7 @@ fn bench_db(c: &mut Criterion) {
   c.bench_function("create_table", |b| {
     b.iter(|| {
       for _ in 0..black_box(1000) {
-        db.create_table("huobi.btc.usdt.1min").unwrap();
+        db.create_table("huobi.btc.usdt.1min", DEFAULT_TABLE_GROUP).unwrap();
       }
     })
   });
@@ -31,5 +33,78 @@ fn bench_db(c: &mut Criterion) {
   });
 }
 
-criterion_group!(benches, bench_db);
+// What `CompactionFilterFactoryImpl::create` did for every compaction before floor keys were
+// tracked in process: open the db read only, then walk back from the tail of each table to its
+// floor key, i.e. the smallest of its newest `keep_latest` keys.
+fn find_floor_keys_with_read_only_open(
+  path: &str, table_ids: &[TableId], keep_latest: u32,
+) -> Vec<Option<Vec<u8>>> {
+  let mut opts = rocksdb::Options::default();
+  opts.set_prefix_extractor(rocksdb::SliceTransform::create_fixed_prefix(4));
+  let rocksdb = rocksdb::DB::open_for_read_only(&opts, path, false).unwrap();
+  table_ids
+    .iter()
+    .map(|&table_id| {
+      let prefix = build_inner_key(table_id, b"");
+      let mut iter = rocksdb.raw_iterator();
+      iter.seek_for_prev(build_tail_anchor(table_id));
+      for _ in 1..keep_latest {
+        if !iter.valid() {
+          break;
+        }
+        iter.prev();
+      }
+      iter
+        .key()
+        .filter(|inner_key| inner_key.starts_with(&prefix))
+        .map(|inner_key| inner_key.to_vec())
+    })
+    .collect()
+}
+
+// `compact_range_with_floor_key_lookups` adds back the lookups the compactions used to do, so the
+// two cases compare the compactions before and after floor keys were tracked in process.
+fn bench_compaction(c: &mut Criterion) {
+  const KEEP_LATEST: u32 = 10;
+  let path = "./data/seriesdb_bench.compaction";
+  let mut opts = Options::new();
+  opts.set_keep_latest(KEEP_LATEST);
+  let db = TtlDb::open(path, 1, &opts).unwrap();
+  let table_ids: Vec<TableId> =
+    (0..100).map(|i| db.open_table(&format!("huobi.btc.usdt.{}", i)).unwrap().id()).collect();
+  let fill = || {
+    for i in 0..black_box(100) {
+      let table = db.open_table(&format!("huobi.btc.usdt.{}", i)).unwrap();
+      let mut wb = table.new_write_batch();
+      for j in 0..black_box(1000_u32) {
+        wb.put_with_expiry(j.to_be_bytes(), b"v", 0);
+      }
+      wb.write().unwrap();
+    }
+  };
+  let mut group = c.benchmark_group("compaction");
+  group.sample_size(10);
+  group.bench_function("compact_range", |b| {
+    b.iter_batched(
+      fill,
+      |_| db.inner().compact_range(None::<&[u8]>, None::<&[u8]>),
+      BatchSize::PerIteration,
+    )
+  });
+  group.bench_function("compact_range_with_floor_key_lookups", |b| {
+    b.iter_batched(
+      fill,
+      |_| {
+        black_box(find_floor_keys_with_read_only_open(path, &table_ids, KEEP_LATEST));
+        db.inner().compact_range(None::<&[u8]>, None::<&[u8]>)
+      },
+      BatchSize::PerIteration,
+    )
+  });
+  group.finish();
+  drop(db);
+  TtlDb::destroy(path).unwrap();
+}
+
+criterion_group!(benches, bench_db, bench_compaction);
 criterion_main!(benches);
@@ -0,0 +1,49 @@
+use std::{env, process};
+
+use seriesdb::convert::*;
+use seriesdb::options::Options;
+
+const USAGE: &str = "Usage: seriesdb_convert [--group <group>]... <normal|ttl> <path> [<dst_path>]";
+
+fn main() {
+  // The groups of the db, which it must be opened with.
+  let mut opts = Options::new();
+  let mut args = Vec::new();
+  let mut iter = env::args().skip(1);
+  while let Some(arg) = iter.next() {
+    if arg != "--group" {
+      args.push(arg);
+    } else if let Some(group) = iter.next() {
+      opts.add_table_group(&group, &Options::new());
+    } else {
+      eprintln!("{}", USAGE);
+      process::exit(2);
+    }
+  }
+  let ttl_enabled = match args.first().map(String::as_str) {
+    Some("normal") => false,
+    Some("ttl") => true,
+    _ => {
+      eprintln!("{}", USAGE);
+      process::exit(2);
+    }
+  };
+  let result = match &args[1..] {
+    [path] => convert_in_place(path, ttl_enabled, &opts),
+    [src_path, dst_path] => convert_into(src_path, dst_path, ttl_enabled, &opts),
+    _ => {
+      eprintln!("{}", USAGE);
+      process::exit(2);
+    }
+  };
+  match result {
+    Ok(0) => (),
+    Ok(unconverted) => {
+      eprintln!("Left {} values which could not be converted as they are", unconverted)
+    }
+    Err(err) => {
+      eprintln!("Failed to convert: {}", err);
+      process::exit(1);
+    }
+  }
+}
@@ -0,0 +1,284 @@
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use rocksdb::{
+  BoundColumnFamily, DBRawIterator, ReadOptions, Snapshot, WriteBatch as RocksdbWriteBatch,
+  WriteBatchIterator, DB as RocksdbDb,
+};
+
+use crate::consts::*;
+use crate::error::Error;
+use crate::types::*;
+use crate::utils::*;
+
+/// The column family holding the data of the tables of a group. `None` stands for the default
+/// one, which also holds the catalog.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ColumnFamily(Option<Arc<str>>);
+
+impl ColumnFamily {
+  /// Returns `Error::TableGroupNotFound` if the db is not opened with the group.
+  #[inline]
+  pub(crate) fn find(inner_db: &RocksdbDb, group: &str) -> Result<Self, Error> {
+    if group == DEFAULT_TABLE_GROUP {
+      Ok(ColumnFamily(None))
+    } else if inner_db.cf_handle(group).is_some() {
+      Ok(ColumnFamily(Some(group.into())))
+    } else {
+      Err(Error::TableGroupNotFound(group.to_string()))
+    }
+  }
+
+  /// Returns the one of the group of the table, as the catalog records it.
+  #[inline]
+  pub(crate) fn of_table(inner_db: &RocksdbDb, table_id: TableId) -> Result<Self, Error> {
+    let group = inner_db.get(build_id_to_group_table_inner_key(table_id))?;
+    Self::of_group_entry(inner_db, group)
+  }
+
+  /// Same as `of_table`, but for the group entry of the table, e.g. as read from a snapshot.
+  #[inline]
+  pub(crate) fn of_group_entry(
+    inner_db: &RocksdbDb, group: Option<Vec<u8>>,
+  ) -> Result<Self, Error> {
+    match group {
+      Some(group) => Self::find(inner_db, &String::from_utf8_lossy(&group)),
+      None => Ok(ColumnFamily(None)),
+    }
+  }
+
+  #[inline]
+  pub(crate) fn group(&self) -> &str {
+    self.0.as_deref().unwrap_or(DEFAULT_TABLE_GROUP)
+  }
+
+  #[inline]
+  pub(crate) fn is_default(&self) -> bool {
+    self.0.is_none()
+  }
+
+  #[inline]
+  pub(crate) fn put<K, V>(
+    &self, inner_db: &RocksdbDb, key: K, value: V,
+  ) -> Result<(), RocksdbError>
+  where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>, {
+    match &self.0 {
+      Some(name) => inner_db.put_cf(&handle(inner_db, name), key, value),
+      None => inner_db.put(key, value),
+    }
+  }
+
+  /// Same as `put`, but adds the put to the batch instead of writing it.
+  #[inline]
+  pub(crate) fn put_into<K, V>(
+    &self, inner_db: &RocksdbDb, batch: &mut RocksdbWriteBatch, key: K, value: V,
+  ) where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>, {
+    match &self.0 {
+      Some(name) => batch.put_cf(&handle(inner_db, name), key, value),
+      None => batch.put(key, value),
+    }
+  }
+
+  #[inline]
+  pub(crate) fn delete<K: AsRef<[u8]>>(
+    &self, inner_db: &RocksdbDb, key: K,
+  ) -> Result<(), RocksdbError> {
+    match &self.0 {
+      Some(name) => inner_db.delete_cf(&handle(inner_db, name), key),
+      None => inner_db.delete(key),
+    }
+  }
+
+  #[inline]
+  pub(crate) fn delete_range<K: AsRef<[u8]>>(
+    &self, inner_db: &RocksdbDb, from: K, to: K,
+  ) -> Result<(), RocksdbError> {
+    match &self.0 {
+      Some(name) => inner_db.delete_range_cf(&handle(inner_db, name), from, to),
+      None => {
+        let mut batch = RocksdbWriteBatch::default();
+        batch.delete_range(from, to);
+        inner_db.write(batch)
+      }
+    }
+  }
+
+  #[inline]
+  pub(crate) fn get<K: AsRef<[u8]>>(
+    &self, inner_db: &RocksdbDb, key: K,
+  ) -> Result<Option<Vec<u8>>, RocksdbError> {
+    match &self.0 {
+      Some(name) => inner_db.get_cf(&handle(inner_db, name), key),
+      None => inner_db.get(key),
+    }
+  }
+
+  #[inline]
+  pub(crate) fn raw_iterator_opt<'a>(
+    &self, inner_db: &'a RocksdbDb, opts: ReadOptions,
+  ) -> DBRawIterator<'a> {
+    match &self.0 {
+      Some(name) => inner_db.raw_iterator_cf_opt(&handle(inner_db, name), opts),
+      None => inner_db.raw_iterator_opt(opts),
+    }
+  }
+
+  #[inline]
+  pub(crate) fn snapshot_raw_iterator_opt<'a>(
+    &self, inner_db: &RocksdbDb, snapshot: &Snapshot<'a>, opts: ReadOptions,
+  ) -> DBRawIterator<'a> {
+    match &self.0 {
+      Some(name) => snapshot.raw_iterator_cf_opt(&handle(inner_db, name), opts),
+      None => snapshot.raw_iterator_opt(opts),
+    }
+  }
+
+  #[inline]
+  pub(crate) fn compact_range<K: AsRef<[u8]>>(&self, inner_db: &RocksdbDb, from: K, to: K) {
+    match &self.0 {
+      Some(name) => inner_db.compact_range_cf(&handle(inner_db, name), Some(from), Some(to)),
+      None => inner_db.compact_range(Some(from), Some(to)),
+    }
+  }
+}
+
+// Column families are never dropped while the db is open, and `ColumnFamily::find` makes sure
+// the db is opened with the group in the first place.
+#[inline]
+fn handle<'a>(inner_db: &'a RocksdbDb, name: &str) -> Arc<BoundColumnFamily<'a>> {
+  inner_db.cf_handle(name).unwrap()
+}
+
+// The progress of a rewrite visiting the groups one after the other: the length of the group
+// name, the name and the last key rewritten in it.
+#[inline]
+pub(crate) fn encode_group_progress(group: &str, last_inner_key: &[u8]) -> Vec<u8> {
+  let group_len = u32_to_u8a4(group.len() as u32);
+  [&group_len[..], group.as_bytes(), last_inner_key].concat()
+}
+
+#[inline]
+pub(crate) fn decode_group_progress(buf: &[u8]) -> Option<(&str, &[u8])> {
+  let group_start = std::mem::size_of::<u32>();
+  let group_end = group_start + u8s_to_u32(buf.get(..group_start)?) as usize;
+  let group = std::str::from_utf8(buf.get(group_start..group_end)?).ok()?;
+  Some((group, &buf[group_end..]))
+}
+
+/// Where the ops of a batch go. Batches are built against the default column family, and moved
+/// to the column families of their tables right before being written.
+#[derive(Clone, Debug)]
+pub(crate) enum Routing {
+  /// To the given column family, i.e. the one of the only table in the batch.
+  To(ColumnFamily),
+  /// To the column family of each table, as the catalog records it.
+  ByTable,
+}
+
+impl Routing {
+  #[inline]
+  pub(crate) fn apply(
+    &self, inner_db: &RocksdbDb, batch: RocksdbWriteBatch,
+  ) -> Result<RocksdbWriteBatch, Error> {
+    if matches!(self, Routing::To(cf) if cf.is_default()) {
+      return Ok(batch);
+    }
+    let mut router = BatchRouter {
+      inner_db,
+      routing: self,
+      cfs: AHashMap::new(),
+      batch: RocksdbWriteBatch::default(),
+      error: None,
+    };
+    batch.iterate(&mut router);
+    match router.error {
+      Some(err) => Err(err),
+      None => Ok(router.batch),
+    }
+  }
+}
+
+struct BatchRouter<'a> {
+  inner_db: &'a RocksdbDb,
+  routing: &'a Routing,
+  cfs: AHashMap<TableId, ColumnFamily>,
+  batch: RocksdbWriteBatch,
+  error: Option<Error>,
+}
+
+impl BatchRouter<'_> {
+  // Returns `None` once the catalog can not be read, after which the batch is dropped anyway.
+  fn route(&mut self, inner_key: &[u8]) -> Option<ColumnFamily> {
+    let table_id = match self.routing {
+      Routing::To(cf) => return Some(cf.clone()),
+      Routing::ByTable if inner_key.len() < TABLE_ID_LEN => return Some(ColumnFamily::default()),
+      Routing::ByTable => u8s_to_u8a4(&inner_key[..TABLE_ID_LEN]),
+    };
+    if table_id < MIN_USERLAND_TABLE_ID {
+      return Some(ColumnFamily::default());
+    }
+    if let Some(cf) = self.cfs.get(&table_id) {
+      return Some(cf.clone());
+    }
+    if self.error.is_some() {
+      return None;
+    }
+    match ColumnFamily::of_table(self.inner_db, table_id) {
+      Ok(cf) => {
+        self.cfs.insert(table_id, cf.clone());
+        Some(cf)
+      }
+      Err(err) => {
+        self.error = Some(err);
+        None
+      }
+    }
+  }
+}
+
+impl WriteBatchIterator for BatchRouter<'_> {
+  #[inline]
+  fn put(&mut self, inner_key: Box<[u8]>, inner_value: Box<[u8]>) {
+    match self.route(&inner_key).map(|cf| cf.0) {
+      Some(Some(name)) => self.batch.put_cf(&handle(self.inner_db, &name), inner_key, inner_value),
+      Some(None) => self.batch.put(inner_key, inner_value),
+      None => (),
+    }
+  }
+
+  #[inline]
+  fn delete(&mut self, inner_key: Box<[u8]>) {
+    match self.route(&inner_key).map(|cf| cf.0) {
+      Some(Some(name)) => self.batch.delete_cf(&handle(self.inner_db, &name), inner_key),
+      Some(None) => self.batch.delete(inner_key),
+      None => (),
+    }
+  }
+
+  // A range never spans tables, so its begin key tells where it goes.
+  #[inline]
+  fn delete_range(&mut self, begin_inner_key: Box<[u8]>, end_inner_key: Box<[u8]>) {
+    match self.route(&begin_inner_key).map(|cf| cf.0) {
+      Some(Some(name)) => {
+        self.batch.delete_range_cf(&handle(self.inner_db, &name), begin_inner_key, end_inner_key)
+      }
+      Some(None) => self.batch.delete_range(begin_inner_key, end_inner_key),
+      None => (),
+    }
+  }
+
+  #[inline]
+  fn merge(&mut self, inner_key: Box<[u8]>, inner_value: Box<[u8]>) {
+    match self.route(&inner_key).map(|cf| cf.0) {
+      Some(Some(name)) => {
+        self.batch.merge_cf(&handle(self.inner_db, &name), inner_key, inner_value)
+      }
+      Some(None) => self.batch.merge(inner_key, inner_value),
+      None => (),
+    }
+  }
+}
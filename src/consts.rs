@@ -2,7 +2,54 @@ use crate::types::*;
 
 pub(crate) const TABLE_ID_LEN: usize = 4;
 
-pub(crate) const TIMESTAMP_LEN: usize = 4;
+pub(crate) const TIMESTAMP_LEN: usize = 8;
+
+pub(crate) const SECS_TIMESTAMP_LEN: usize = 4;
+
+pub(crate) const HEADER_KIND_LEN: usize = 1;
+
+// The value expires ttl seconds after its timestamp, which is in seconds.
+pub(crate) const SECS_TIMESTAMPED_HEADER_KIND: u8 = 0;
+
+pub(crate) const SECS_TIMESTAMPED_HEADER_LEN: usize = HEADER_KIND_LEN + SECS_TIMESTAMP_LEN;
+
+// The value carries its own expiry right after its timestamp, both in seconds.
+pub(crate) const SECS_EXPIRING_HEADER_KIND: u8 = 1;
+
+pub(crate) const SECS_EXPIRING_HEADER_LEN: usize = HEADER_KIND_LEN + SECS_TIMESTAMP_LEN * 2;
+
+// The value expires ttl seconds after its timestamp, which is in milliseconds.
+pub(crate) const TIMESTAMPED_HEADER_KIND: u8 = 2;
+
+pub(crate) const TIMESTAMPED_HEADER_LEN: usize = HEADER_KIND_LEN + TIMESTAMP_LEN;
+
+// The value carries its own expiry right after its timestamp, both in milliseconds.
+pub(crate) const EXPIRING_HEADER_KIND: u8 = 3;
+
+pub(crate) const EXPIRING_HEADER_LEN: usize = HEADER_KIND_LEN + TIMESTAMP_LEN * 2;
+
+// The value expires ttl seconds after the timestamp derived from its key.
+pub(crate) const KEY_TIMESTAMPED_HEADER_KIND: u8 = 4;
+
+pub(crate) const KEY_TIMESTAMPED_HEADER_LEN: usize = HEADER_KIND_LEN;
+
+// Values of ttl dbs are prefixed with a tagged header since format 1, and new values are written
+// with millisecond headers since format 2. Headers in seconds are still read.
+pub(crate) const MILLIS_VALUE_FORMAT: u8 = 2;
+
+pub(crate) const VALUE_FORMAT_UPGRADE_BATCH_SIZE: usize = 1024;
+
+pub(crate) const CONVERSION_BATCH_SIZE: usize = 1024;
+
+pub(crate) const COPY_BATCH_SIZE: usize = 1024;
+
+pub(crate) const DEFAULT_KEEP_LATEST: u32 = 1;
+
+pub(crate) const NAMESPACE_SEPARATOR: char = '/';
+
+// The group of the tables created by `open_table`, i.e. RocksDB's default column family, which
+// also holds the catalog.
+pub const DEFAULT_TABLE_GROUP: &str = "default";
 
 // 1024 as BigEndian
 pub(crate) const MIN_USERLAND_TABLE_ID: TableId = [0, 0, 4, 0];
@@ -19,8 +66,38 @@ pub(crate) const NAME_TO_ID_TABLE_ID: TableId = [0, 0, 0, 1];
 // 2 as BigEndian
 pub(crate) const ID_TO_NAME_TABLE_ID: TableId = [0, 0, 0, 2];
 
+// 3 as BigEndian
+pub(crate) const ID_TO_TTL_TABLE_ID: TableId = [0, 0, 0, 3];
+
+// 4 as BigEndian
+pub(crate) const ID_TO_KEEP_LATEST_TABLE_ID: TableId = [0, 0, 0, 4];
+
+// 5 as BigEndian
+pub(crate) const ID_TO_RETENTION_TABLE_ID: TableId = [0, 0, 0, 5];
+
+// 6 as BigEndian
+pub(crate) const ID_TO_META_TABLE_ID: TableId = [0, 0, 0, 6];
+
+// 7 as BigEndian
+pub(crate) const FREE_ID_TABLE_ID: TableId = [0, 0, 0, 7];
+
+// 8 as BigEndian
+pub(crate) const ALIAS_TO_ID_TABLE_ID: TableId = [0, 0, 0, 8];
+
+// 9 as BigEndian. Tables of the default group have no entry.
+pub(crate) const ID_TO_GROUP_TABLE_ID: TableId = [0, 0, 0, 9];
+
 // 0 as BigEndian. Use this to fix wal bug.
 pub(crate) const PLACEHOLDER_ITEM_ID: ItemId = [0, 0];
 
 // 1 as BigEndian. Use this to ensure consistent open.
 pub(crate) const TTL_ITEM_ID: ItemId = [0, 1];
+
+// 2 as BigEndian. Use this to record the layout of ttl values.
+pub(crate) const VALUE_FORMAT_ITEM_ID: ItemId = [0, 2];
+
+// 3 as BigEndian. Use this to resume an interrupted upgrade of ttl values.
+pub(crate) const VALUE_FORMAT_UPGRADE_PROGRESS_ITEM_ID: ItemId = [0, 3];
+
+// 4 as BigEndian. Use this to resume an interrupted conversion between normal and ttl dbs.
+pub(crate) const CONVERSION_PROGRESS_ITEM_ID: ItemId = [0, 4];
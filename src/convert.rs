@@ -0,0 +1,280 @@
+use std::{path::Path, sync::Arc};
+
+use bytes::Bytes;
+use rocksdb::{ReadOptions, WriteBatch as RocksdbWriteBatch, DB as RocksdbDb};
+
+use crate::column_family::*;
+use crate::consts::*;
+use crate::db::*;
+use crate::error::Error;
+use crate::options::Options;
+use crate::utils::*;
+
+/// Rewrites the db at the given path in place, so it opens as a ttl db if `ttl_enabled`, or as a
+/// normal db otherwise. Table ids and names are kept, and values which become ttl values are
+/// timestamped with the time of the conversion. The groups of the db must be added to `opts`. An
+/// interrupted conversion resumes where it stopped when called again with the same `ttl_enabled`.
+/// The ttls and numbers of newest records to keep of the tables are dropped along with the ttl.
+/// Returns the number of ttl values which could not be converted as their headers are corrupted,
+/// and which are left as they are, out of those visited by this call.
+pub fn convert_in_place<P: AsRef<Path>>(
+  path: P, ttl_enabled: bool, opts: &Options,
+) -> Result<u64, Error> {
+  let inner_db = Arc::new(open(path, opts)?);
+  convert(inner_db.clone(), &inner_db, &opts.list_table_groups(), ttl_enabled, false)
+}
+
+/// Same as `convert_in_place`, but writes the converted db to `dst_path`. The db at `src_path`
+/// is left as it is, except that the values of an outdated ttl db are upgraded first. The values
+/// which could not be converted are copied as they are.
+pub fn convert_into<P: AsRef<Path>, Q: AsRef<Path>>(
+  src_path: P, dst_path: Q, ttl_enabled: bool, opts: &Options,
+) -> Result<u64, Error> {
+  let src_db = Arc::new(open(src_path, opts)?);
+  let dst_db = open(dst_path, opts)?;
+  convert(src_db, &dst_db, &opts.list_table_groups(), ttl_enabled, true)
+}
+
+#[inline]
+fn open<P: AsRef<Path>>(path: P, opts: &Options) -> Result<RocksdbDb, Error> {
+  Ok(RocksdbDb::open_cf_descriptors(&opts.inner, path, opts.build_column_family_descriptors())?)
+}
+
+// Userland values are rewritten in batches which also record the group and the last key
+// rewritten in it, together with the wanted kind of db. When copying into another db, the other
+// keys are copied as they are, except for the info items written once the conversion is done.
+// The ttl overrides of the tables are deleted in the last batch when converting to a normal db.
+fn convert(
+  src_db: Arc<RocksdbDb>, dst_db: &RocksdbDb, groups: &[&str], ttl_enabled: bool, copy_all: bool,
+) -> Result<u64, Error> {
+  let ttl_item_inner_key = build_info_table_inner_key(TTL_ITEM_ID);
+  let value_format_item_inner_key = build_info_table_inner_key(VALUE_FORMAT_ITEM_ID);
+  let progress_item_inner_key = build_info_table_inner_key(CONVERSION_PROGRESS_ITEM_ID);
+  let ttl_flag = ttl_enabled as u8;
+
+  let progress = dst_db.get(&progress_item_inner_key)?;
+  match &progress {
+    Some(progress) if progress[0] != ttl_flag => {
+      return Err(Error::InconsistentTtlEnabled { current: progress[0] == 1, wanted: ttl_enabled })
+    }
+    Some(_) => (),
+    None => {
+      if dst_db.get(&ttl_item_inner_key)? == Some(vec![ttl_flag]) {
+        return Ok(0);
+      }
+      if src_db.get(&ttl_item_inner_key)? == Some(vec![1]) {
+        TtlDb::ensure_value_format_upgraded(src_db.clone(), groups)?;
+      }
+      dst_db.put(&progress_item_inner_key, [ttl_flag])?;
+    }
+  }
+  let resumed = progress.as_deref().and_then(|progress| decode_group_progress(&progress[1..]));
+  let first_group = match resumed {
+    Some((group, _)) => match groups.iter().position(|candidate| *candidate == group) {
+      Some(position) => position,
+      None => return Err(Error::TableGroupNotFound(group.to_string())),
+    },
+    None => 0,
+  };
+
+  let skipped_inner_keys: Vec<Bytes> = [
+    TTL_ITEM_ID,
+    VALUE_FORMAT_ITEM_ID,
+    VALUE_FORMAT_UPGRADE_PROGRESS_ITEM_ID,
+    CONVERSION_PROGRESS_ITEM_ID,
+  ]
+  .into_iter()
+  .map(build_info_table_inner_key)
+  .collect();
+  let timestamp = u64_to_u8a8(now());
+  let mut batch = RocksdbWriteBatch::default();
+  let mut unconverted = 0;
+  for (position, group) in groups.iter().enumerate().skip(first_group) {
+    let src_cf = ColumnFamily::find(&src_db, group)?;
+    let dst_cf = ColumnFamily::find(dst_db, group)?;
+    let mut opts = ReadOptions::default();
+    opts.set_total_order_seek(true);
+    let mut iter = src_cf.raw_iterator_opt(&src_db, opts);
+    match resumed {
+      Some((_, last_inner_key)) if position == first_group => {
+        iter.seek(last_inner_key);
+        if iter.key() == Some(last_inner_key) {
+          iter.next();
+        }
+      }
+      _ if copy_all => iter.seek_to_first(),
+      _ => iter.seek(MIN_USERLAND_TABLE_ID),
+    }
+
+    while iter.valid() {
+      let inner_key = iter.key().unwrap();
+      let inner_value = iter.value().unwrap();
+      if inner_key >= &MIN_USERLAND_TABLE_ID[..] {
+        if ttl_enabled {
+          let value = build_timestamped_value(timestamp, inner_value);
+          dst_cf.put_into(dst_db, &mut batch, inner_key, &value[..]);
+        } else if let Some(value) = extract_value(inner_value) {
+          dst_cf.put_into(dst_db, &mut batch, inner_key, value);
+        } else {
+          unconverted += 1;
+          if copy_all {
+            dst_cf.put_into(dst_db, &mut batch, inner_key, inner_value);
+          }
+        }
+      } else if copy_all && !skipped_inner_keys.iter().any(|skipped| skipped == inner_key) {
+        dst_cf.put_into(dst_db, &mut batch, inner_key, inner_value);
+      }
+      if batch.len() >= CONVERSION_BATCH_SIZE {
+        batch.put(&progress_item_inner_key, encode_progress(ttl_flag, group, inner_key));
+        dst_db.write(std::mem::take(&mut batch))?;
+      }
+      iter.next();
+    }
+    iter.status()?;
+  }
+
+  if unconverted > 0 {
+    log::warn!("Left values which could not be converted as they are: count: {}", unconverted);
+  }
+  batch.put(&ttl_item_inner_key, [ttl_flag]);
+  if ttl_enabled {
+    batch.put(&value_format_item_inner_key, [MILLIS_VALUE_FORMAT]);
+  } else {
+    batch.delete(&value_format_item_inner_key);
+    for system_table_id in [ID_TO_TTL_TABLE_ID, ID_TO_KEEP_LATEST_TABLE_ID] {
+      batch.delete_range(build_head_anchor(system_table_id), build_tail_anchor(system_table_id));
+    }
+  }
+  batch.delete(&progress_item_inner_key);
+  dst_db.write(batch)?;
+  Ok(unconverted)
+}
+
+// The wanted kind of db, then the group and the last key. Only the kind is recorded before
+// anything is rewritten.
+#[inline]
+fn encode_progress(ttl_flag: u8, group: &str, last_inner_key: &[u8]) -> Vec<u8> {
+  [&[ttl_flag][..], &encode_group_progress(group, last_inner_key)].concat()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::table::*;
+  use crate::types::*;
+
+  #[test]
+  fn test_convert_in_place() {
+    let path = "./data/convert.test_convert_in_place";
+    let name = "huobi.btc.usdt.1min";
+    let id: TableId;
+    {
+      let db = NormalDb::open(path, &Options::new()).unwrap();
+      let table = db.open_table(name).unwrap();
+      id = table.id();
+      table.put(b"k1", b"v1").unwrap();
+      table.put(b"k2", b"v2").unwrap();
+    }
+    assert_eq!(convert_in_place(path, true, &Options::new()).unwrap(), 0);
+    assert!(NormalDb::open(path, &Options::new()).is_err());
+    {
+      let db = TtlDb::open(path, 60, &Options::new()).unwrap();
+      let table = db.open_table(name).unwrap();
+      assert_eq!(table.id(), id);
+      assert_eq!(&*table.get(b"k1").unwrap().unwrap(), b"v1");
+      table.put(b"k3", b"v3").unwrap();
+      db.set_table_ttl(name, 120).unwrap();
+      db.set_table_keep_latest(name, 2).unwrap();
+      // Of an unknown kind, so it can not be converted.
+      db.inner.put(build_inner_key(id, b"k4"), [9]).unwrap();
+    }
+    assert_eq!(convert_in_place(path, false, &Options::new()).unwrap(), 1);
+    {
+      let db = NormalDb::open(path, &Options::new()).unwrap();
+      let table = db.open_table(name).unwrap();
+      assert_eq!(table.id(), id);
+      for (key, value) in [(b"k1", b"v1"), (b"k2", b"v2"), (b"k3", b"v3")] {
+        assert_eq!(&*table.get(key).unwrap().unwrap(), value);
+      }
+      assert_eq!(&*table.get(b"k4").unwrap().unwrap(), [9]);
+      assert!(db.inner.get(build_id_to_ttl_table_inner_key(id)).unwrap().is_none());
+      assert!(db.inner.get(build_id_to_keep_latest_table_inner_key(id)).unwrap().is_none());
+    }
+    assert!(NormalDb::destroy(path).is_ok());
+  }
+
+  #[test]
+  fn test_convert_into() {
+    let src_path = "./data/convert.test_convert_into.src";
+    let dst_path = "./data/convert.test_convert_into.dst";
+    let name = "huobi.btc.usdt.1min";
+    {
+      let db = TtlDb::open(src_path, 60, &Options::new()).unwrap();
+      db.open_table(name).unwrap().put(b"k1", b"v1").unwrap();
+    }
+    convert_into(src_path, dst_path, false, &Options::new()).unwrap();
+    {
+      let db = NormalDb::open(dst_path, &Options::new()).unwrap();
+      assert_eq!(&*db.open_table(name).unwrap().get(b"k1").unwrap().unwrap(), b"v1");
+    }
+    assert!(TtlDb::open(src_path, 60, &Options::new()).is_ok());
+    assert!(NormalDb::destroy(src_path).is_ok());
+    assert!(NormalDb::destroy(dst_path).is_ok());
+  }
+
+  #[test]
+  fn test_convert_table_groups() {
+    let path = "./data/convert.test_convert_table_groups";
+    let dst_path = "./data/convert.test_convert_table_groups.dst";
+    let name = "huobi.btc.usdt.1min";
+    let mut opts = Options::new();
+    opts.add_table_group("cold", &Options::new());
+    {
+      let db = NormalDb::open(path, &opts).unwrap();
+      db.open_table_in_group(name, "cold").unwrap().put(b"k1", b"v1").unwrap();
+      db.open_table("huobi.eth.usdt.1min").unwrap().put(b"k1", b"v2").unwrap();
+    }
+    assert!(convert_in_place(path, true, &Options::new()).is_err());
+    convert_in_place(path, true, &opts).unwrap();
+    {
+      let db = TtlDb::open(path, 60, &opts).unwrap();
+      assert_eq!(&*db.open_table(name).unwrap().get(b"k1").unwrap().unwrap(), b"v1");
+      assert_eq!(
+        &*db.open_table("huobi.eth.usdt.1min").unwrap().get(b"k1").unwrap().unwrap(),
+        b"v2"
+      );
+    }
+    convert_into(path, dst_path, false, &opts).unwrap();
+    {
+      let db = NormalDb::open(dst_path, &opts).unwrap();
+      assert_eq!(db.get_table_group(name).unwrap().unwrap(), "cold");
+      assert_eq!(&*db.open_table(name).unwrap().get(b"k1").unwrap().unwrap(), b"v1");
+    }
+    assert!(NormalDb::destroy(path).is_ok());
+    assert!(NormalDb::destroy(dst_path).is_ok());
+  }
+
+  #[test]
+  fn test_resume_conversion() {
+    let path = "./data/convert.test_resume_conversion";
+    let name = "huobi.btc.usdt.1min";
+    {
+      let db = NormalDb::open(path, &Options::new()).unwrap();
+      let table = db.open_table(name).unwrap();
+      let converted_value = build_timestamped_value(u64_to_u8a8(now()), b"v1");
+      table.put(b"k1", converted_value).unwrap();
+      table.put(b"k2", b"v2").unwrap();
+      let progress = encode_progress(1, DEFAULT_TABLE_GROUP, &build_inner_key(table.id(), b"k1"));
+      db.inner.put(build_info_table_inner_key(CONVERSION_PROGRESS_ITEM_ID), progress).unwrap();
+    }
+    assert!(convert_in_place(path, false, &Options::new()).is_err());
+    convert_in_place(path, true, &Options::new()).unwrap();
+    {
+      let db = TtlDb::open(path, 60, &Options::new()).unwrap();
+      let table = db.open_table(name).unwrap();
+      assert_eq!(&*table.get(b"k1").unwrap().unwrap(), b"v1");
+      assert_eq!(&*table.get(b"k2").unwrap().unwrap(), b"v2");
+    }
+    assert!(NormalDb::destroy(path).is_ok());
+  }
+}
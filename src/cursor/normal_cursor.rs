@@ -2,6 +2,7 @@ use bytes::Bytes;
 use rocksdb::DBRawIterator;
 
 use crate::cursor::*;
+use crate::error::Error;
 use crate::types::*;
 use crate::utils::*;
 
@@ -9,6 +10,7 @@ pub struct NormalCursor<'a> {
   pub(crate) inner: DBRawIterator<'a>,
   pub(crate) table_id: TableId,
   pub(crate) tail_anchor: &'a Bytes,
+  pub(crate) table_destroyed: bool,
 }
 
 impl<'a> Cursor<'a> for NormalCursor<'a> {
@@ -38,8 +40,25 @@ impl<'a> Cursor<'a> for NormalCursor<'a> {
   ////////////////////////////////////////////////////////////////////////////////
   /// APIs
   ////////////////////////////////////////////////////////////////////////////////
+  /// A cursor of a destroyed table is never valid, and `status` tells why.
+  #[inline]
+  fn is_valid(&self) -> bool {
+    !self.table_destroyed && self.inner.valid()
+  }
+
+  #[inline]
+  fn status(&self) -> Result<(), Error> {
+    if self.table_destroyed {
+      return Err(Error::TableDestroyed(u8a4_to_u32(self.table_id)));
+    }
+    Ok(self.inner.status()?)
+  }
+
   #[inline]
   fn key(&self) -> Option<&[u8]> {
+    if self.table_destroyed {
+      return None;
+    }
     if let Some(v) = self.inner.key() {
       Some(extract_key(v))
     } else {
@@ -49,6 +68,9 @@ impl<'a> Cursor<'a> for NormalCursor<'a> {
 
   #[inline]
   fn value(&self) -> Option<&[u8]> {
+    if self.table_destroyed {
+      return None;
+    }
     self.inner.value()
   }
 }
@@ -56,7 +78,7 @@ impl<'a> Cursor<'a> for NormalCursor<'a> {
 impl<'a> NormalCursor<'a> {
   #[inline]
   pub fn new(inner: DBRawIterator<'a>, table_id: TableId, anchor: &'a Bytes) -> Self {
-    NormalCursor { inner, table_id, tail_anchor: anchor }
+    NormalCursor { inner, table_id, tail_anchor: anchor, table_destroyed: false }
   }
 }
 
@@ -2,6 +2,10 @@ use bytes::Bytes;
 use rocksdb::DBRawIterator;
 
 use crate::cursor::*;
+use crate::error::Error;
+use crate::floor_key_index::Floor;
+use crate::table::*;
+use crate::ttl_registry::*;
 use crate::types::*;
 use crate::utils::*;
 
@@ -9,6 +13,9 @@ pub struct TtlCursor<'a> {
   pub(crate) inner: DBRawIterator<'a>,
   pub(crate) table_id: TableId,
   pub(crate) tail_anchor: &'a Bytes,
+  pub(crate) expiry_filter: ExpiryFilter,
+  pub(crate) hide_expired: bool,
+  pub(crate) table_destroyed: bool,
 }
 
 impl<'a> Cursor<'a> for TtlCursor<'a> {
@@ -38,8 +45,66 @@ impl<'a> Cursor<'a> for TtlCursor<'a> {
   ////////////////////////////////////////////////////////////////////////////////
   /// APIs
   ////////////////////////////////////////////////////////////////////////////////
+  /// Same as for RocksDB corruptions, the cursor turns invalid on a corrupted value, and `status`
+  /// tells which. A cursor of a destroyed table is never valid either.
+  #[inline]
+  fn is_valid(&self) -> bool {
+    !self.table_destroyed && self.inner.valid() && !self.is_corrupted()
+  }
+
+  #[inline]
+  fn status(&self) -> Result<(), Error> {
+    if self.table_destroyed {
+      return Err(Error::TableDestroyed(u8a4_to_u32(self.table_id)));
+    }
+    self.inner.status()?;
+    if self.is_corrupted() {
+      return Err(Error::CorruptedValue(self.inner.key().unwrap().to_vec()));
+    }
+    Ok(())
+  }
+
+  #[inline]
+  fn seek_to_first(&mut self) {
+    self.inner.seek(self.table_id);
+    self.skip_expired_forward();
+  }
+
+  #[inline]
+  fn seek_to_last(&mut self) {
+    self.inner.seek_for_prev(self.tail_anchor);
+    self.skip_expired_backward();
+  }
+
+  #[inline]
+  fn seek<K: AsRef<[u8]>>(&mut self, key: K) {
+    self.inner.seek(build_inner_key(self.table_id, key));
+    self.skip_expired_forward();
+  }
+
+  #[inline]
+  fn seek_for_prev<K: AsRef<[u8]>>(&mut self, key: K) {
+    self.inner.seek_for_prev(build_inner_key(self.table_id, key));
+    self.skip_expired_backward();
+  }
+
+  #[inline]
+  fn next(&mut self) {
+    self.inner.next();
+    self.skip_expired_forward();
+  }
+
+  #[inline]
+  fn prev(&mut self) {
+    self.inner.prev();
+    self.skip_expired_backward();
+  }
+
   #[inline]
   fn key(&self) -> Option<&[u8]> {
+    if self.table_destroyed {
+      return None;
+    }
     if let Some(v) = self.inner.key() {
       Some(extract_key(v))
     } else {
@@ -49,28 +114,113 @@ impl<'a> Cursor<'a> for TtlCursor<'a> {
 
   #[inline]
   fn value(&self) -> Option<&[u8]> {
-    if let Some(value) = self.inner.value() {
-      Some(extract_value(value))
-    } else {
-      None
+    if self.table_destroyed {
+      return None;
     }
+    self.inner.value().and_then(extract_value)
   }
 }
 
 impl<'a> TtlCursor<'a> {
+  /// Builds a cursor which does not hide expired values, and knows of no per-table settings, i.e.
+  /// key timestamp extractors. Prefer `TtlTable::new_cursor`.
   #[inline]
   pub fn new(inner: DBRawIterator<'a>, table_id: TableId, anchor: &'a Bytes) -> Self {
-    TtlCursor { inner, table_id, tail_anchor: anchor }
+    Self::with_expiry_filter(inner, table_id, anchor, ExpiryFilter::none(), false)
   }
 
   #[inline]
-  pub fn timestamped_value(&self) -> Option<(u32, &[u8])> {
+  pub(crate) fn with_expiry_filter(
+    inner: DBRawIterator<'a>, table_id: TableId, anchor: &'a Bytes, expiry_filter: ExpiryFilter,
+    hide_expired: bool,
+  ) -> Self {
+    TtlCursor {
+      inner,
+      table_id,
+      tail_anchor: anchor,
+      expiry_filter,
+      hide_expired,
+      table_destroyed: false,
+    }
+  }
+
+  /// The timestamp is in milliseconds. It is 0 for a value whose timestamp is derived from its key
+  /// while no extractor is registered for the table.
+  #[inline]
+  pub fn timestamped_value(&self) -> Option<(u64, &[u8])> {
     if let Some(value) = self.inner.value() {
-      Some((u8s_to_u32(extract_timestamp(value)), extract_value(value)))
+      let key = extract_key(self.inner.key().unwrap());
+      let timestamp = self.expiry_filter.rule.get_timestamp(key, value);
+      Some((timestamp.unwrap_or(0), extract_value(value)?))
     } else {
       None
     }
   }
+
+  #[inline]
+  fn is_corrupted(&self) -> bool {
+    self.inner.value().is_some_and(|value| extract_header_len(value).is_none())
+  }
+
+  #[inline]
+  fn skip_expired_forward(&mut self) {
+    if self.hide_expired {
+      while self.inner.valid()
+        && self
+          .expiry_filter
+          .is_expired(extract_key(self.inner.key().unwrap()), self.inner.value().unwrap())
+      {
+        self.inner.next();
+      }
+    }
+  }
+
+  #[inline]
+  fn skip_expired_backward(&mut self) {
+    if self.hide_expired {
+      while self.inner.valid()
+        && self
+          .expiry_filter
+          .is_expired(extract_key(self.inner.key().unwrap()), self.inner.value().unwrap())
+      {
+        self.inner.prev();
+      }
+    }
+  }
+}
+
+/// Hides the values a compaction would remove: expired values of every key but the newest ones.
+pub(crate) struct ExpiryFilter {
+  rule: ExpiryRule,
+  floor: Floor,
+  now: u64,
+}
+
+impl ExpiryFilter {
+  #[inline]
+  pub(crate) fn new(table: &TtlTable) -> Self {
+    ExpiryFilter {
+      rule: table.ttl_registry.get_expiry_rule(table.id),
+      floor: table.ttl_registry.get_floor(table.id),
+      now: now(),
+    }
+  }
+
+  // Hides nothing.
+  #[inline]
+  pub(crate) fn none() -> Self {
+    ExpiryFilter { rule: ExpiryRule::default(), floor: Floor::Everything, now: 0 }
+  }
+
+  #[inline]
+  pub(crate) fn is_expired(&self, key: &[u8], timestamped_value: &[u8]) -> bool {
+    if extract_header_len(timestamped_value).is_none()
+      || self.rule.get_expires_at(key, timestamped_value) >= self.now
+    {
+      return false;
+    }
+    !self.floor.protects(key)
+  }
 }
 
 #[cfg(test)]
@@ -82,6 +232,49 @@ mod tests {
   use crate::setup_with_ttl;
   use crate::table::*;
 
+  #[test]
+  fn test_skip_expired() {
+    use std::{thread, time};
+
+    setup_with_ttl!("ttl_cursor.test_skip_expired"; 1; db);
+
+    let name = "huobi.btc.usdt.1m";
+    let table = db.open_table(name).unwrap();
+    assert!(table.put(b"k1", b"v1").is_ok());
+    assert!(table.put(b"k2", b"v2").is_ok());
+    assert!(table.put_with_ttl(b"k3", b"v3", 60).is_ok());
+    assert!(table.put(b"k4", b"v4").is_ok());
+    assert!(table.put(b"k5", b"v5").is_ok());
+
+    thread::sleep(time::Duration::from_secs(2));
+
+    let mut cursor = table.new_cursor();
+    cursor.seek_to_first();
+    assert_eq!(b"k3", cursor.key().unwrap());
+    cursor.next();
+    assert_eq!(b"k5", cursor.key().unwrap());
+    cursor.next();
+    assert!(!cursor.is_valid());
+
+    cursor.seek_to_last();
+    assert_eq!(b"k5", cursor.key().unwrap());
+    cursor.prev();
+    assert_eq!(b"k3", cursor.key().unwrap());
+    cursor.prev();
+    assert!(!cursor.is_valid());
+
+    cursor.seek(b"k1");
+    assert_eq!(b"k3", cursor.key().unwrap());
+    cursor.seek_for_prev(b"k4");
+    assert_eq!(b"k3", cursor.key().unwrap());
+    cursor.seek_for_prev(b"k2");
+    assert!(!cursor.is_valid());
+
+    let mut cursor = table.new_cursor_including_expired();
+    cursor.seek_to_first();
+    assert_eq!(b"k1", cursor.key().unwrap());
+  }
+
   #[test]
   fn test_seek() {
     setup_with_ttl!("ttl_cursor.test_seek"; 3; db);
@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::Ordering;
+
+use prost::Message;
+use rocksdb::{ReadOptions, WriteBatch};
+
+use super::db::Db;
+use crate::column_family::ColumnFamily;
+use crate::consts::*;
+use crate::cursor::*;
+use crate::error::Error;
+use crate::table::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// A problem found by `Db::check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+  /// A catalog entry which can not be decoded.
+  CorruptedEntry { inner_key: Vec<u8> },
+  /// A name to id entry without the matching id to name one.
+  DanglingName { name: String, id: u32 },
+  /// An id to name entry without the matching name to id one.
+  DanglingId { id: u32, name: String },
+  /// An alias whose target is not a registered table.
+  DanglingAlias { alias: String, id: u32 },
+  /// A per-table record, e.g. a ttl, of an id which is not a registered table.
+  OrphanRecord { inner_key: Vec<u8> },
+  /// A freed id which is a registered table.
+  FreeIdInUse { id: u32 },
+  /// Data under an id which is not a registered table, in the column family of the group.
+  OrphanData { id: u32, group: String },
+  /// Fresh ids would start at or below an id in use.
+  StaleLastTableId { last_table_id: u32, max_table_id: u32 },
+}
+
+/// What `Db::repair` does with orphan data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairMode {
+  /// Deletes the data and frees its id.
+  DropOrphans,
+  /// Registers the data as a table named `orphan.<id>`, in the group holding it.
+  Reregister,
+}
+
+// The catalog lock must be held.
+pub(crate) fn check<D: Db + ?Sized>(db: &D) -> Result<Vec<Finding>, Error> {
+  let mut findings = Vec::new();
+
+  let mut name_to_id = BTreeMap::new();
+  for (key, value) in scan(db, NAME_TO_ID_TABLE_ID)? {
+    match (String::from_utf8(key.clone()), value.len() == TABLE_ID_LEN) {
+      (Ok(name), true) => {
+        name_to_id.insert(name, u8s_to_u32(&value));
+      }
+      _ => findings.push(corrupted_entry(NAME_TO_ID_TABLE_ID, &key)),
+    }
+  }
+  let mut id_to_name = BTreeMap::new();
+  for (key, value) in scan(db, ID_TO_NAME_TABLE_ID)? {
+    match (key.len() == TABLE_ID_LEN, String::from_utf8(value)) {
+      (true, Ok(name)) => {
+        id_to_name.insert(u8s_to_u32(&key), name);
+      }
+      _ => findings.push(corrupted_entry(ID_TO_NAME_TABLE_ID, &key)),
+    }
+  }
+  let mut registered = BTreeSet::new();
+  for (name, &id) in &name_to_id {
+    if id_to_name.get(&id) == Some(name) {
+      registered.insert(id);
+    } else {
+      findings.push(Finding::DanglingName { name: name.clone(), id });
+    }
+  }
+  for (&id, name) in &id_to_name {
+    if !registered.contains(&id) {
+      findings.push(Finding::DanglingId { id, name: name.clone() });
+    }
+  }
+
+  for (key, value) in scan(db, ALIAS_TO_ID_TABLE_ID)? {
+    match (String::from_utf8(key.clone()), value.len() == TABLE_ID_LEN) {
+      (Ok(alias), true) if !registered.contains(&u8s_to_u32(&value)) => {
+        findings.push(Finding::DanglingAlias { alias, id: u8s_to_u32(&value) })
+      }
+      (Ok(_), true) => (),
+      _ => findings.push(corrupted_entry(ALIAS_TO_ID_TABLE_ID, &key)),
+    }
+  }
+  for table_id in [
+    ID_TO_TTL_TABLE_ID,
+    ID_TO_KEEP_LATEST_TABLE_ID,
+    ID_TO_RETENTION_TABLE_ID,
+    ID_TO_META_TABLE_ID,
+    ID_TO_GROUP_TABLE_ID,
+  ] {
+    for (key, _) in scan(db, table_id)? {
+      if key.len() != TABLE_ID_LEN || !registered.contains(&u8s_to_u32(&key)) {
+        findings.push(Finding::OrphanRecord { inner_key: build_inner_key(table_id, key).to_vec() });
+      }
+    }
+  }
+  let mut max_table_id = u8a4_to_u32(MIN_USERLAND_TABLE_ID) - 1;
+  for (key, _) in scan(db, FREE_ID_TABLE_ID)? {
+    if key.len() != TABLE_ID_LEN {
+      findings.push(corrupted_entry(FREE_ID_TABLE_ID, &key));
+      continue;
+    }
+    let id = u8s_to_u32(&key);
+    if registered.contains(&id) {
+      findings.push(Finding::FreeIdInUse { id });
+    }
+    max_table_id = max_table_id.max(id);
+  }
+
+  let groups = db.opts().table_groups.iter().map(|(group, _)| group.as_str());
+  for group in std::iter::once(DEFAULT_TABLE_GROUP).chain(groups) {
+    for id in get_data_table_ids(db, &ColumnFamily::find(db.inner(), group)?)? {
+      if !registered.contains(&id) {
+        findings.push(Finding::OrphanData { id, group: group.to_string() });
+      }
+      max_table_id = max_table_id.max(id);
+    }
+  }
+  max_table_id = max_table_id.max(registered.last().copied().unwrap_or_default());
+  let last_table_id = db.last_table_id().load(Ordering::SeqCst);
+  if last_table_id < max_table_id {
+    findings.push(Finding::StaleLastTableId { last_table_id, max_table_id });
+  }
+  Ok(findings)
+}
+
+// The catalog lock must be held. The repairs are written in one batch.
+pub(crate) fn repair<D: Db + ?Sized>(db: &D, mode: RepairMode) -> Result<Vec<Finding>, Error> {
+  let findings = check(db)?;
+  let reregistered: BTreeSet<u32> = match mode {
+    RepairMode::DropOrphans => BTreeSet::new(),
+    RepairMode::Reregister => findings
+      .iter()
+      .filter_map(|finding| match finding {
+        Finding::OrphanData { id, .. } => Some(*id),
+        _ => None,
+      })
+      .collect(),
+  };
+
+  let mut batch = WriteBatch::default();
+  let mut dropped_ids = Vec::new();
+  let mut dropped_orphans = Vec::new();
+  let mut reregistered_ids = BTreeSet::new();
+  let mut stale_names = Vec::new();
+  for finding in &findings {
+    match finding {
+      Finding::CorruptedEntry { inner_key } => batch.delete(inner_key),
+      Finding::DanglingName { name, .. } => {
+        batch.delete(build_name_to_id_table_inner_key(name));
+        stale_names.push(name.clone());
+      }
+      Finding::DanglingId { id, .. } => {
+        batch.delete(build_id_to_name_table_inner_key(u32_to_u8a4(*id)))
+      }
+      Finding::DanglingAlias { alias, .. } => {
+        batch.delete(build_alias_to_id_table_inner_key(alias));
+        stale_names.push(alias.clone());
+      }
+      Finding::OrphanRecord { inner_key } => {
+        let key = extract_key(inner_key);
+        if key.len() != TABLE_ID_LEN || !reregistered.contains(&u8s_to_u32(key)) {
+          batch.delete(inner_key);
+        }
+      }
+      Finding::FreeIdInUse { id } => batch.delete(build_free_id_table_inner_key(u32_to_u8a4(*id))),
+      Finding::OrphanData { id, group } => {
+        let id = u32_to_u8a4(*id);
+        match mode {
+          RepairMode::DropOrphans => {
+            dropped_orphans.push((ColumnFamily::find(db.inner(), group)?, id));
+            batch.put(build_free_id_table_inner_key(id), []);
+            dropped_ids.push(id);
+          }
+          // Data of the same id in several groups is left to the next check.
+          RepairMode::Reregister if !reregistered_ids.insert(id) => (),
+          RepairMode::Reregister => {
+            let name = generate_orphan_name(db, id)?;
+            batch.delete(build_free_id_table_inner_key(id));
+            batch.put(build_name_to_id_table_inner_key(&name), id);
+            batch.put(build_id_to_name_table_inner_key(id), &name);
+            if group != DEFAULT_TABLE_GROUP {
+              batch.put(build_id_to_group_table_inner_key(id), group);
+            }
+            let meta_inner_key = build_id_to_meta_table_inner_key(id);
+            if db.inner().get(&meta_inner_key)?.is_none() {
+              let now = now();
+              let meta = TableMeta { created_at: now, modified_at: now, ..Default::default() };
+              batch.put(meta_inner_key, meta.encode_to_vec());
+            }
+          }
+        }
+      }
+      Finding::StaleLastTableId { max_table_id, .. } => {
+        db.last_table_id().fetch_max(*max_table_id, Ordering::SeqCst);
+      }
+    }
+  }
+  // Orphans have no group entry, so the batch can not route the deletes of their data, which
+  // are written first instead. A crash in between only leaks their ids, whereas freeing the ids
+  // first could hand them out with the data still there.
+  for (cf, id) in dropped_orphans {
+    cf.delete_range(db.inner(), build_head_anchor(id), build_tail_anchor(id))?;
+  }
+  let result = db.write_inner(batch);
+  for name in &stale_names {
+    db.cache().remove(name);
+  }
+  result?;
+  for id in dropped_ids {
+    db.on_table_destroyed(id);
+  }
+  Ok(findings)
+}
+
+#[inline]
+fn corrupted_entry(table_id: TableId, key: &[u8]) -> Finding {
+  Finding::CorruptedEntry { inner_key: build_inner_key(table_id, key).to_vec() }
+}
+
+type Entry = (Vec<u8>, Vec<u8>);
+
+fn scan<D: Db + ?Sized>(db: &D, table_id: TableId) -> Result<Vec<Entry>, Error> {
+  let mut result = Vec::new();
+  let table = NormalTable::new(db.inner().clone(), table_id);
+  let mut cursor = table.new_cursor();
+  cursor.seek_to_first();
+  while cursor.is_valid() {
+    result.push((cursor.key().unwrap().to_vec(), cursor.value().unwrap().to_vec()));
+    cursor.next();
+  }
+  cursor.status()?;
+  Ok(result)
+}
+
+// Visits one key per userland table, skipping to the next id right after.
+fn get_data_table_ids<D: Db + ?Sized>(db: &D, cf: &ColumnFamily) -> Result<BTreeSet<u32>, Error> {
+  let mut result = BTreeSet::new();
+  let mut opts = ReadOptions::default();
+  opts.set_total_order_seek(true);
+  let mut iter = cf.raw_iterator_opt(db.inner(), opts);
+  iter.seek(MIN_USERLAND_TABLE_ID);
+  while iter.valid() {
+    let inner_key = iter.key().unwrap();
+    if inner_key.len() < TABLE_ID_LEN {
+      iter.next();
+      continue;
+    }
+    let id = u8s_to_u32(&inner_key[..TABLE_ID_LEN]);
+    result.insert(id);
+    if id == u32::MAX {
+      break;
+    }
+    iter.seek(u32_to_u8a4(id + 1));
+  }
+  iter.status()?;
+  Ok(result)
+}
+
+fn generate_orphan_name<D: Db + ?Sized>(db: &D, id: TableId) -> Result<String, Error> {
+  let mut name = format!("orphan.{}", u8a4_to_u32(id));
+  let mut suffix = 0;
+  while db.resolve_table_id(&name)?.is_some() {
+    suffix += 1;
+    name = format!("orphan.{}.{}", u8a4_to_u32(id), suffix);
+  }
+  Ok(name)
+}
@@ -2,18 +2,27 @@ use std::{
   path::Path,
   sync::{
     atomic::{AtomicU32, Ordering},
-    Arc,
+    Arc, Mutex,
   },
 };
 
+use bytes::Bytes;
 use concurrent_initializer::{ConcurrentInitializer, InitResult};
+use prost::Message;
 use quick_cache::{sync::Cache, Weighter};
 use rocksdb::{ReadOptions, WriteBatch, DB as RocksdbDb};
 
+use super::check::{self, Finding, RepairMode};
+use super::namespace::{build_namespace_prefix, Namespace};
+use super::open_mode::OpenMode;
+use super::table_name_iterator::TableNameIterator;
+use crate::column_family::*;
 use crate::consts::*;
 use crate::cursor::*;
 use crate::error::Error;
 use crate::options::Options;
+use crate::retention::*;
+use crate::rollup::*;
 use crate::table::*;
 use crate::types::*;
 use crate::utils::*;
@@ -37,8 +46,17 @@ pub trait Db {
 
   fn initializer(&self) -> &ConcurrentInitializer<String, TableId>;
 
+  // Serializes the changes to the catalog, i.e. the name to id and id to name tables.
+  fn catalog_lock(&self) -> &Mutex<()>;
+
+  fn live_tables(&self) -> &LiveTables;
+
+  fn retention_counts(&self) -> &RetentionCounts;
+
   fn opts(&self) -> &Options;
 
+  fn rollups(&self) -> &Arc<RollupRegistry>;
+
   ////////////////////////////////////////////////////////////////////////////////
   /// APIs
   ////////////////////////////////////////////////////////////////////////////////
@@ -47,42 +65,157 @@ pub trait Db {
     Ok(RocksdbDb::destroy(&Options::new().inner, path)?)
   }
 
+  /// The name may also be an alias, in which case its target is opened. A table created is put
+  /// in the default group.
   #[inline]
   fn open_table(&self, name: &str) -> Result<Arc<Self::Table>, Error> {
-    if let Some(table) = self.cache().get(name) {
-      return Ok(table);
-    } else {
-      let table = Arc::new(if let Some(id) = self.get_table_id_by_name(name)? {
-        self.new_table(id)
-      } else {
-        self.create_table(name)?
-      });
-      self.cache().insert(name.to_string(), table.clone());
-      return Ok(table);
-    }
+    self.open_table_opt(name, OpenMode::OpenOrCreate, None)
   }
 
+  /// Same as `open_table` with `OpenMode::OpenOrCreate`. Otherwise returns
+  /// `Error::TableNotFound` or `Error::TableAlreadyExists` if the table does not exist, or does,
+  /// respectively.
+  #[inline]
+  fn open_table_with_mode(&self, name: &str, mode: OpenMode) -> Result<Arc<Self::Table>, Error> {
+    self.open_table_opt(name, mode, None)
+  }
+
+  /// Same as `open_table`, but a table created is put in the given group, i.e. its data lives in
+  /// the column family of the group. Returns `Error::TableGroupNotFound` if the db is not opened
+  /// with the group, or `Error::TableGroupMismatch` if the table exists in another one.
+  #[inline]
+  fn open_table_in_group(&self, name: &str, group: &str) -> Result<Arc<Self::Table>, Error> {
+    self.open_table_opt(name, OpenMode::OpenOrCreate, Some(group))
+  }
+
+  /// Returns `Error::TableNotFound` if there is no such table. The aliases of the table are
+  /// dropped too.
   fn destroy_table(&self, name: &str) -> Result<(), Error> {
-    let mut batch = WriteBatch::default();
-    if let Some(id) = self.get_table_id_by_name(name)? {
-      batch.delete(&build_name_to_id_table_inner_key(name));
-      batch.delete(&build_id_to_name_table_inner_key(id));
-      batch.delete_range(build_head_anchor(id).as_ref(), build_tail_anchor(id).as_ref());
-    }
-    let result = self.inner().write(batch);
-    self.cache().remove(name);
-    result.map_err(|err| Error::RocksdbError(err))
+    let _guard = self.catalog_lock().lock().unwrap();
+    let id = match self.get_table_id_by_name(name)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(name.to_string())),
+    };
+    self.destroy_tables(vec![(name.to_string(), id)])
   }
 
+  /// Rollups derived from the table are emptied too. Returns `Error::TableNotFound` if there is
+  /// no such table.
   fn truncate_table(&self, name: &str) -> Result<(), Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    let id = match self.get_table_id_by_name(name)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(name.to_string())),
+    };
     let mut batch = WriteBatch::default();
-    if let Some(id) = self.get_table_id_by_name(name)? {
-      batch.delete_range(build_head_anchor(id).as_ref(), build_tail_anchor(id).as_ref());
+    batch.delete_range(build_head_anchor(id).as_ref(), build_tail_anchor(id).as_ref());
+    self.touch_table_meta(&mut batch, id)?;
+    self.retention_counts().remove(id);
+    let routing = self.opts().routing();
+    self.rollups().write(self.inner(), batch, &routing, |batch, _| self.write_inner(batch))
+  }
+
+  /// Returns a view over the tables in the namespace, i.e. those named `<namespace>/<name>`.
+  #[inline]
+  fn namespace<'a>(&'a self, namespace: &str) -> Namespace<'a, Self>
+  where Self: Sized {
+    Namespace::new(self, namespace)
+  }
+
+  /// Destroys every table in the namespace at once.
+  fn drop_namespace(&self, namespace: &str) -> Result<(), Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    let mut tables = Vec::new();
+    for result in self.iter_tables(&build_namespace_prefix(namespace), None) {
+      let (name, id) = result?;
+      tables.push((name, u32_to_u8a4(id)));
     }
-    Ok(self.inner().write(batch)?)
+    self.destroy_tables(tables)
   }
 
+  /// Returns the outermost namespaces holding at least one table, in name order.
+  fn get_namespaces(&self) -> Result<Vec<String>, Error> {
+    let mut result = Vec::new();
+    let mut iter = self.iter_tables("", None);
+    while let Some(item) = iter.next() {
+      if let Some((namespace, _)) = item?.0.split_once(NAMESPACE_SEPARATOR) {
+        // Skips the rest of the namespace, as its names sort before this one.
+        iter.seek(&format!("{}{}", namespace, (NAMESPACE_SEPARATOR as u8 + 1) as char));
+        result.push(namespace.to_string());
+      }
+    }
+    Ok(result)
+  }
+
+  /// Copies every record of the table `src` into a new table `dst`, as of a snapshot taken at the
+  /// start. Values are copied as they are, so ttl headers are kept. Returns
+  /// `Error::TableNotFound` if there is no table named `src`, or `Error::TableAlreadyExists` if
+  /// there is one named `dst`.
+  #[inline]
+  fn copy_table(&self, src: &str, dst: &str) -> Result<(), Error>
+  where Self: Sized {
+    self.copy_table_into(src, self, dst)
+  }
+
+  /// Same as `copy_table`, but the new table is created in `dst_db`, which must have ttl enabled
+  /// as this db has, or not. The new table is put in the group of `src`, which `dst_db` must be
+  /// opened with. If the copy fails, the new table is destroyed again. A crash during the copy
+  /// still leaves it partly filled, so it has to be destroyed before the copy is retried.
+  fn copy_table_into<D: Db>(&self, src: &str, dst_db: &D, dst: &str) -> Result<(), Error> {
+    let ttl_item_inner_key = build_info_table_inner_key(TTL_ITEM_ID);
+    let current = self.inner().get(&ttl_item_inner_key)? == Some(vec![1]);
+    let wanted = dst_db.inner().get(&ttl_item_inner_key)? == Some(vec![1]);
+    if current != wanted {
+      return Err(Error::InconsistentTtlEnabled { current, wanted });
+    }
+
+    let snapshot = self.inner().snapshot();
+    let src_id = match snapshot.get(build_name_to_id_table_inner_key(src))? {
+      Some(id) => u8s_to_u8a4(&id),
+      None => return Err(Error::TableNotFound(src.to_string())),
+    };
+    let src_group = snapshot.get(build_id_to_group_table_inner_key(src_id))?;
+    let src_cf = ColumnFamily::of_group_entry(self.inner(), src_group)?;
+    let dst_id = dst_db.open_table_opt(dst, OpenMode::CreateNew, Some(src_cf.group()))?.id();
+
+    let copy = || -> Result<(), Error> {
+      let tail_anchor = build_tail_anchor(src_id);
+      let mut opts = ReadOptions::default();
+      opts.set_prefix_same_as_start(true);
+      let mut iter = src_cf.snapshot_raw_iterator_opt(self.inner(), &snapshot, opts);
+      iter.seek(build_head_anchor(src_id));
+      let mut batch = WriteBatch::default();
+      while iter.valid() && iter.key().unwrap() < &tail_anchor[..] {
+        batch.put(build_inner_key(dst_id, extract_key(iter.key().unwrap())), iter.value().unwrap());
+        if batch.len() >= COPY_BATCH_SIZE {
+          dst_db.write_inner(std::mem::take(&mut batch))?;
+        }
+        iter.next();
+      }
+      iter.status()?;
+      if !batch.is_empty() {
+        dst_db.write_inner(batch)?;
+      }
+      Ok(())
+    };
+    // The records are written in several batches, so a failed copy would leave `dst` half
+    // filled. It is destroyed instead, so the copy can simply be retried.
+    let result = copy();
+    if result.is_err() {
+      if let Err(err) = dst_db.destroy_table(dst) {
+        log::error!("Failed to destroy a partial copy: name: {:?}, err: {:?}", dst, err);
+      }
+    }
+    result
+  }
+
+  /// Returns `Error::TableNotFound` if there is no table named `old_name`, or
+  /// `Error::TableAlreadyExists` if there is one named `new_name`.
   fn rename_table(&self, old_name: &str, new_name: &str) -> Result<(), Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    if self.resolve_table_id(new_name)?.is_some() {
+      return Err(Error::TableAlreadyExists(new_name.to_string()));
+    }
     let mut batch = WriteBatch::default();
     if let Some(id) = self.get_table_id_by_name(old_name)? {
       let id_to_name_table_inner_key = build_id_to_name_table_inner_key(id);
@@ -90,13 +223,80 @@ pub trait Db {
       batch.delete(&id_to_name_table_inner_key);
       batch.put(build_name_to_id_table_inner_key(new_name), id);
       batch.put(id_to_name_table_inner_key, new_name);
+      self.touch_table_meta(&mut batch, id)?;
+    } else {
+      return Err(Error::TableNotFound(old_name.to_string()));
     }
     let result = self.inner().write(batch);
     self.cache().remove(old_name);
     result.map_err(|err| Error::RocksdbError(err))
   }
 
-  fn get_table_infos(&self) -> Vec<(String, u32)> {
+  /// Adds an alias, i.e. an extra name which `open_table` resolves to the target table. Returns
+  /// `Error::TableNotFound` if there is no such target, or `Error::TableAlreadyExists` if the
+  /// alias is taken by a table or another alias.
+  fn create_alias(&self, alias: &str, target: &str) -> Result<(), Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    if self.resolve_table_id(alias)?.is_some() {
+      return Err(Error::TableAlreadyExists(alias.to_string()));
+    }
+    let id = match self.get_table_id_by_name(target)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(target.to_string())),
+    };
+    Ok(self.inner().put(build_alias_to_id_table_inner_key(alias), id)?)
+  }
+
+  /// Points the alias at another table at once, so readers switch from the old target to the new
+  /// one with no gap. Returns `Error::AliasNotFound` or `Error::TableNotFound` if there is no
+  /// such alias, or target, respectively.
+  fn swap_alias(&self, alias: &str, new_target: &str) -> Result<(), Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    if self.get_table_id_by_alias(alias)?.is_none() {
+      return Err(Error::AliasNotFound(alias.to_string()));
+    }
+    let id = match self.get_table_id_by_name(new_target)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(new_target.to_string())),
+    };
+    let mut batch = WriteBatch::default();
+    batch.put(build_alias_to_id_table_inner_key(alias), id);
+    let result = self.inner().write(batch);
+    self.cache().remove(alias);
+    Ok(result?)
+  }
+
+  /// Returns `Error::AliasNotFound` if there is no such alias.
+  fn drop_alias(&self, alias: &str) -> Result<(), Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    if self.get_table_id_by_alias(alias)?.is_none() {
+      return Err(Error::AliasNotFound(alias.to_string()));
+    }
+    let result = self.inner().delete(build_alias_to_id_table_inner_key(alias));
+    self.cache().remove(alias);
+    Ok(result?)
+  }
+
+  /// Returns the aliases along with the names of their targets, in alias order.
+  fn get_alias_infos(&self) -> Result<Vec<(String, String)>, Error> {
+    let mut result = Vec::new();
+    let alias_to_id_table = NormalTable::new(self.inner().clone(), ALIAS_TO_ID_TABLE_ID);
+    let mut cursor = alias_to_id_table.new_cursor();
+    cursor.seek_to_first();
+    while cursor.is_valid() {
+      let alias = std::str::from_utf8(cursor.key().unwrap())?.to_string();
+      let id = u8s_to_u8a4(cursor.value().unwrap());
+      if let Some(target) = self.get_table_name_by_id(id)? {
+        result.push((alias, target));
+      }
+      cursor.next();
+    }
+    cursor.status()?;
+    Ok(result)
+  }
+
+  /// Returns `Error::InvalidTableName` if a name is not valid utf-8.
+  fn get_table_infos(&self) -> Result<Vec<(String, u32)>, Error> {
     let mut result: Vec<(String, u32)> = Vec::new();
     let mut opts = ReadOptions::default();
     opts.set_prefix_same_as_start(true);
@@ -106,11 +306,84 @@ pub trait Db {
       let key = iter.key().unwrap();
       let value = iter.value().unwrap();
       let id = u8s_to_u32(extract_key(key));
-      let name = std::str::from_utf8(value).unwrap().to_string();
+      let name = std::str::from_utf8(value)?.to_string();
       result.push((name, id));
       iter.next();
     }
-    result
+    iter.status()?;
+    Ok(result)
+  }
+
+  /// Iterates the tables whose names start with the prefix, in name order, after `start_after`
+  /// if given.
+  #[inline]
+  fn iter_tables(&self, prefix: &str, start_after: Option<&str>) -> TableNameIterator<'_> {
+    let mut opts = ReadOptions::default();
+    opts.set_prefix_same_as_start(true);
+    TableNameIterator::new(self.inner().raw_iterator_opt(opts), prefix, None, start_after)
+  }
+
+  /// Same as `iter_tables`, but for the tables whose names match the pattern, in which `*`
+  /// stands for any run of characters and `?` for any single one.
+  #[inline]
+  fn iter_tables_matching(
+    &self, pattern: &str, start_after: Option<&str>,
+  ) -> TableNameIterator<'_> {
+    let prefix = &pattern[..pattern.find(['*', '?']).unwrap_or(pattern.len())];
+    let mut opts = ReadOptions::default();
+    opts.set_prefix_same_as_start(true);
+    TableNameIterator::new(self.inner().raw_iterator_opt(opts), prefix, Some(pattern), start_after)
+  }
+
+  /// Returns the names and ids of at most `limit` tables, as `iter_tables` yields them. Pass the
+  /// last name returned as `start_after` to get the next page.
+  #[inline]
+  fn list_tables(
+    &self, prefix: &str, start_after: Option<&str>, limit: usize,
+  ) -> Result<Vec<(String, u32)>, Error> {
+    self.iter_tables(prefix, start_after).take(limit).collect()
+  }
+
+  /// Same as `list_tables`, but as `iter_tables_matching` yields them.
+  #[inline]
+  fn list_tables_matching(
+    &self, pattern: &str, start_after: Option<&str>, limit: usize,
+  ) -> Result<Vec<(String, u32)>, Error> {
+    self.iter_tables_matching(pattern, start_after).take(limit).collect()
+  }
+
+  /// Same as `get_table_infos`, but also returns the metadata of the tables.
+  fn get_table_infos_with_meta(&self) -> Result<Vec<TableInfo>, Error> {
+    let mut result = Vec::new();
+    for (name, id) in self.get_table_infos()? {
+      let meta = self.get_table_meta_by_id(u32_to_u8a4(id))?;
+      result.push(TableInfo { name, id, meta });
+    }
+    Ok(result)
+  }
+
+  /// Returns `None` if there is no such table. Tables created before metadata was recorded get
+  /// an empty one.
+  #[inline]
+  fn get_table_meta(&self, name: &str) -> Result<Option<TableMeta>, Error> {
+    match self.get_table_id_by_name(name)? {
+      Some(id) => Ok(Some(self.get_table_meta_by_id(id)?)),
+      None => Ok(None),
+    }
+  }
+
+  /// Replaces the description, coder and properties of the given table. The times are
+  /// maintained by the db, so the ones passed in are ignored. Returns `Error::TableNotFound` if
+  /// there is no such table.
+  fn set_table_meta(&self, name: &str, meta: TableMeta) -> Result<(), Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    let id = match self.get_table_id_by_name(name)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(name.to_string())),
+    };
+    let created_at = self.get_table_meta_by_id(id)?.created_at;
+    let meta = TableMeta { created_at, modified_at: now(), ..meta };
+    Ok(self.inner().put(build_id_to_meta_table_inner_key(id), meta.encode_to_vec())?)
   }
 
   #[inline]
@@ -123,16 +396,159 @@ pub trait Db {
     }
   }
 
+  /// Returns `None` if there is no such table.
+  #[inline]
+  fn get_table_group(&self, name: &str) -> Result<Option<String>, Error> {
+    match self.get_table_id_by_name(name)? {
+      Some(id) => Ok(Some(self.get_table_group_by_id(id)?)),
+      None => Ok(None),
+    }
+  }
+
+  #[inline]
+  fn get_table_id_by_alias(&self, alias: &str) -> Result<Option<TableId>, Error> {
+    if let Some(id) = self.inner().get(build_alias_to_id_table_inner_key(alias))? {
+      Ok(Some(u8s_to_u8a4(id.as_ref())))
+    } else {
+      Ok(None)
+    }
+  }
+
   #[inline]
   fn get_table_name_by_id(&self, id: TableId) -> Result<Option<String>, Error> {
     let id_to_name_table_inner_key = build_id_to_name_table_inner_key(id);
     if let Some(name) = self.inner().get(id_to_name_table_inner_key)? {
-      Ok(Some(std::str::from_utf8(name.as_ref()).unwrap().to_string()))
+      Ok(Some(std::str::from_utf8(name.as_ref())?.to_string()))
     } else {
       Ok(None)
     }
   }
 
+  /// Caps the size and the number of records of the given table. The caps are enforced by
+  /// `enforce_retention`. Returns `Error::TableNotFound` if there is no such table.
+  fn set_table_retention(&self, name: &str, policy: RetentionPolicy) -> Result<(), Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    let id = match self.get_table_id_by_name(name)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(name.to_string())),
+    };
+    Ok(self.inner().put(build_id_to_retention_table_inner_key(id), policy.encode())?)
+  }
+
+  fn clear_table_retention(&self, name: &str) -> Result<(), Error> {
+    if let Some(id) = self.get_table_id_by_name(name)? {
+      self.inner().delete(build_id_to_retention_table_inner_key(id))?;
+    }
+    Ok(())
+  }
+
+  #[inline]
+  fn get_table_retention(&self, name: &str) -> Result<Option<RetentionPolicy>, Error> {
+    if let Some(id) = self.get_table_id_by_name(name)? {
+      if let Some(policy) = self.inner().get(build_id_to_retention_table_inner_key(id))? {
+        return Ok(Some(RetentionPolicy::decode(&policy)?));
+      }
+    }
+    Ok(None)
+  }
+
+  /// Deletes the oldest records of every table exceeding its retention policy, then compacts
+  /// the deleted ranges. A `RetentionWorker` calls this periodically.
+  fn enforce_retention(&self) -> Result<(), Error> {
+    let mut policies = Vec::new();
+    let id_to_retention_table = NormalTable::new(self.inner().clone(), ID_TO_RETENTION_TABLE_ID);
+    let mut cursor = id_to_retention_table.new_cursor();
+    cursor.seek_to_first();
+    while cursor.is_valid() {
+      policies.push((
+        u8s_to_u8a4(cursor.key().unwrap()),
+        RetentionPolicy::decode(cursor.value().unwrap())?,
+      ));
+      cursor.next();
+    }
+    cursor.status()?;
+    for (id, policy) in policies {
+      self.enforce_table_retention(id, policy)?;
+    }
+    Ok(())
+  }
+
+  /// Keeps the table `target` derived from `source`: every write to `source` through a table or
+  /// a batch recomputes the buckets of `target` it touches, in the same batch, merging the entries
+  /// of each bucket. Writes to `target` update the tables derived from it in turn. Both tables
+  /// are created if needed. The rollup lasts until the db is closed, so it must be registered
+  /// again on every open, and data written before it is picked up by `rebuild_rollup`. Returns
+  /// `Error::InvalidRollup` if `target` is `source`, is derived from another table already, or
+  /// derives `source`, directly or not.
+  ///
+  /// While any rollup is registered, writes to sources are serialized db-wide, and every bucket
+  /// they touch is read again whole from the source, so wide buckets make each such write as slow
+  /// as reading them. Retention updates the buckets of what it deletes, but records expired by
+  /// ttl compactions are not seen, so targets of ttl sources keep them until `rebuild_rollup`.
+  fn register_rollup(
+    &self, source: &str, target: &str, bucketing: Arc<dyn KeyBucketing>,
+    merge: Arc<dyn RollupMerge>,
+  ) -> Result<(), Error> {
+    let source_table = self.open_table(source)?;
+    let target_table = self.open_table(target)?;
+    let rollup = Rollup {
+      source_id: source_table.id(),
+      source_cf: ColumnFamily::find(self.inner(), source_table.group())?,
+      target_id: target_table.id(),
+      target_cf: ColumnFamily::find(self.inner(), target_table.group())?,
+      bucketing,
+      merge,
+    };
+    if self.rollups().register(rollup) {
+      Ok(())
+    } else {
+      Err(Error::InvalidRollup { from: source.to_string(), to: target.to_string() })
+    }
+  }
+
+  /// Stops deriving the table, keeping what it holds. Returns `Error::RollupNotFound` if no
+  /// rollup derives it.
+  fn unregister_rollup(&self, target: &str) -> Result<(), Error> {
+    match self.resolve_table_id(target)? {
+      Some(id) if self.rollups().unregister(id) => Ok(()),
+      _ => Err(Error::RollupNotFound(target.to_string())),
+    }
+  }
+
+  /// Recomputes the table from scratch out of the source of its rollup, replacing what it holds,
+  /// in one batch. Writes to the source racing with it may be missing from the table until
+  /// their buckets are written again. Returns `Error::RollupNotFound` if no rollup derives it.
+  fn rebuild_rollup(&self, target: &str) -> Result<(), Error> {
+    let rollup = match self.resolve_table_id(target)? {
+      Some(id) => self.rollups().get_by_target(id),
+      None => None,
+    };
+    let rollup = rollup.ok_or_else(|| Error::RollupNotFound(target.to_string()))?;
+    let mut batch = self.new_write_batch_x();
+    let id = rollup.target_id;
+    batch.inner_mut().delete_range(build_head_anchor(id), build_tail_anchor(id));
+    for (key, value) in self.rollups().build_buckets(self.inner(), &rollup)? {
+      batch.inner_mut().put(build_inner_key(id, key), value);
+    }
+    batch.write()
+  }
+
+  /// Verifies that the name to id and id to name tables form a bijection, and that every other
+  /// record and every data range belongs to a registered table. Changes nothing.
+  #[inline]
+  fn check(&self) -> Result<Vec<Finding>, Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    check::check(self)
+  }
+
+  /// Fixes what `check` finds, and returns it. Dangling and corrupted entries are deleted, while
+  /// orphan data is dropped or re-registered according to the mode.
+  #[inline]
+  fn repair(&self, mode: RepairMode) -> Result<Vec<Finding>, Error> {
+    let _guard = self.catalog_lock().lock().unwrap();
+    check::repair(self, mode)
+  }
+
   #[inline]
   fn get_latest_sn(&self) -> u64 {
     self.inner().latest_sequence_number()
@@ -144,6 +560,8 @@ pub trait Db {
     Ok(WriteOpBatchIterator::new(iter))
   }
 
+  /// Applies the batches in order. The ops are put in the column families of the groups of their
+  /// tables in this db, so their `cf_id`s need not match the ones here.
   fn replay(&self, write_op_batches: Vec<WriteOpBatch>) -> Result<u64, Error> {
     let mut sn = 0;
     for write_op_batch in write_op_batches {
@@ -161,12 +579,12 @@ pub trait Db {
           }
         }
       }
-      self.inner().write(batch)?;
+      self.write_inner(batch)?;
     }
     Ok(sn)
   }
 
-  fn new_table(&self, id: TableId) -> Self::Table;
+  fn new_table(&self, id: TableId, group: &str) -> Result<Self::Table, Error>;
 
   fn new_write_batch_x(&self) -> Self::WriteBatchX;
 
@@ -200,27 +618,189 @@ pub trait Db {
     }
   }
 
+  // Freed ids count too, so the id of a destroyed table is not handed out fresh again.
   #[doc(hidden)]
   #[inline]
   fn get_last_table_id(inner_db: Arc<RocksdbDb>) -> Result<u32, Error> {
-    let id_to_name_table = NormalTable::new(inner_db.clone(), ID_TO_NAME_TABLE_ID);
-    let mut cusor = id_to_name_table.new_cursor();
-    cusor.seek_to_last();
-    if cusor.is_valid() {
-      Ok(u8s_to_u32(cusor.key().unwrap()))
+    let mut last_table_id = u8a4_to_u32(MIN_USERLAND_TABLE_ID) - 1;
+    for table_id in [ID_TO_NAME_TABLE_ID, FREE_ID_TABLE_ID] {
+      let table = NormalTable::new(inner_db.clone(), table_id);
+      let mut cusor = table.new_cursor();
+      cusor.seek_to_last();
+      if cusor.is_valid() {
+        last_table_id = last_table_id.max(u8s_to_u32(cusor.key().unwrap()));
+      } else {
+        cusor.status()?;
+      }
+    }
+    Ok(last_table_id)
+  }
+
+  // The catalog lock must be held. The tables are destroyed in one batch.
+  #[doc(hidden)]
+  fn destroy_tables(&self, tables: Vec<(String, TableId)>) -> Result<(), Error> {
+    let mut batch = WriteBatch::default();
+    let mut ids = Vec::with_capacity(tables.len());
+    let mut names = Vec::with_capacity(tables.len());
+    for (name, id) in tables {
+      batch.delete(&build_name_to_id_table_inner_key(&name));
+      batch.delete(&build_id_to_name_table_inner_key(id));
+      for inner_key in [
+        build_id_to_ttl_table_inner_key(id),
+        build_id_to_keep_latest_table_inner_key(id),
+        build_id_to_retention_table_inner_key(id),
+        build_id_to_meta_table_inner_key(id),
+        build_id_to_group_table_inner_key(id),
+      ] {
+        if self.inner().get(&inner_key)?.is_some() {
+          batch.delete(&inner_key);
+        }
+      }
+      batch.delete_range(build_head_anchor(id).as_ref(), build_tail_anchor(id).as_ref());
+      batch.put(build_free_id_table_inner_key(id), []);
+      for alias in self.get_aliases_by_id(id)? {
+        batch.delete(build_alias_to_id_table_inner_key(&alias));
+        names.push(alias);
+      }
+      names.push(name);
+      ids.push(id);
+    }
+    let result = self.live_tables().destroy(&ids, || self.write_inner(batch));
+    for name in &names {
+      self.cache().remove(name);
+    }
+    result?;
+    // Writes to the sources of the derived tables may have put buckets back until their rollups
+    // are removed, so their ranges are deleted again. The ids are not reused before, as the
+    // catalog lock is held.
+    let mut batch = WriteBatch::default();
+    for id in ids {
+      if self.rollups().remove_table(id) {
+        batch.delete_range(build_head_anchor(id).as_ref(), build_tail_anchor(id).as_ref());
+      }
+      self.retention_counts().remove(id);
+      self.on_table_destroyed(id);
+    }
+    if batch.is_empty() {
+      return Ok(());
+    }
+    self.write_inner(batch)
+  }
+
+  #[doc(hidden)]
+  #[inline]
+  fn get_cached_table(&self, name: &str) -> Option<Arc<Self::Table>> {
+    let table = self.cache().get(name)?;
+    if table.is_alive() {
+      Some(table)
     } else {
-      match cusor.status() {
-        Ok(_) => Ok(u8a4_to_u32(MIN_USERLAND_TABLE_ID) - 1),
-        Err(e) => Err(e),
+      self.cache().remove(name);
+      None
+    }
+  }
+
+  // Catalog changes evict the names they touch only after writing, so a handle resolved before
+  // one of them is dropped here instead of staying cached.
+  #[doc(hidden)]
+  #[inline]
+  fn cache_table(&self, name: &str, table: &Arc<Self::Table>) -> Result<bool, Error> {
+    self.cache().insert(name.to_string(), table.clone());
+    if table.is_alive()
+      && self.resolve_table_id(name)? == Some(table.id())
+      && self.get_table_group_by_id(table.id())? == table.group()
+    {
+      Ok(true)
+    } else {
+      self.cache().remove(name);
+      Ok(false)
+    }
+  }
+
+  // Tables and aliases share their names.
+  #[doc(hidden)]
+  #[inline]
+  fn resolve_table_id(&self, name: &str) -> Result<Option<TableId>, Error> {
+    match self.get_table_id_by_name(name)? {
+      Some(id) => Ok(Some(id)),
+      None => self.get_table_id_by_alias(name),
+    }
+  }
+
+  #[doc(hidden)]
+  fn get_aliases_by_id(&self, id: TableId) -> Result<Vec<String>, Error> {
+    let mut result = Vec::new();
+    let alias_to_id_table = NormalTable::new(self.inner().clone(), ALIAS_TO_ID_TABLE_ID);
+    let mut cursor = alias_to_id_table.new_cursor();
+    cursor.seek_to_first();
+    while cursor.is_valid() {
+      if cursor.value().unwrap() == id {
+        result.push(String::from_utf8_lossy(cursor.key().unwrap()).into_owned());
+      }
+      cursor.next();
+    }
+    cursor.status()?;
+    Ok(result)
+  }
+
+  #[doc(hidden)]
+  fn open_table_opt(
+    &self, name: &str, mode: OpenMode, group: Option<&str>,
+  ) -> Result<Arc<Self::Table>, Error> {
+    if let Some(group) = group {
+      ColumnFamily::find(self.inner(), group)?;
+    }
+    let table = match mode {
+      OpenMode::CreateNew => {
+        let _guard = self.catalog_lock().lock().unwrap();
+        if self.resolve_table_id(name)?.is_some() {
+          return Err(Error::TableAlreadyExists(name.to_string()));
+        }
+        let group = group.unwrap_or(DEFAULT_TABLE_GROUP);
+        let table = Arc::new(self.new_table(self.register_new_table(name, group)?, group)?);
+        self.cache().insert(name.to_string(), table.clone());
+        return Ok(table);
       }
+      OpenMode::OpenExisting | OpenMode::OpenOrCreate => loop {
+        if let Some(table) = self.get_cached_table(name) {
+          break table;
+        }
+        let table = Arc::new(match self.resolve_table_id(name)? {
+          Some(id) => self.new_table(id, &self.get_table_group_by_id(id)?)?,
+          None if mode == OpenMode::OpenExisting => {
+            return Err(Error::TableNotFound(name.to_string()))
+          }
+          None => self.create_table(name, group.unwrap_or(DEFAULT_TABLE_GROUP))?,
+        });
+        if self.cache_table(name, &table)? {
+          break table;
+        }
+      },
+    };
+    match group {
+      Some(group) if group != table.group() => Err(Error::TableGroupMismatch {
+        name: name.to_string(),
+        current: table.group().to_string(),
+        wanted: group.to_string(),
+      }),
+      _ => Ok(table),
     }
   }
 
+  // Tables of the default group have no entry.
   #[doc(hidden)]
-  fn create_table(&self, name: &str) -> Result<Self::Table, Error> {
+  #[inline]
+  fn get_table_group_by_id(&self, id: TableId) -> Result<String, Error> {
+    match self.inner().get(build_id_to_group_table_inner_key(id))? {
+      Some(group) => Ok(String::from_utf8_lossy(&group).into_owned()),
+      None => Ok(DEFAULT_TABLE_GROUP.to_string()),
+    }
+  }
+
+  #[doc(hidden)]
+  fn create_table(&self, name: &str, group: &str) -> Result<Self::Table, Error> {
     let result = self.initializer().try_get_or_init(
       &Arc::new(name.to_owned()),
-      || match self.get_table_id_by_name(name) {
+      || match self.resolve_table_id(name) {
         Ok(ok) => {
           if let Some(id) = ok {
             return Ok(Some(id));
@@ -233,43 +813,187 @@ pub trait Db {
         }
       },
       || {
-        let id = self.generate_next_table_id()?;
-        let name_to_id_table_inner_key = build_name_to_id_table_inner_key(name);
-        let id_to_name_table_inner_key = build_id_to_name_table_inner_key(id);
-        self.register_table(name_to_id_table_inner_key, id, id_to_name_table_inner_key, name)?;
-        Ok(id)
+        let _guard = self.catalog_lock().lock().unwrap();
+        if let Some(id) = self.resolve_table_id(name)? {
+          return Ok(id);
+        }
+        self.register_new_table(name, group)
       },
     );
+    // The table may have been created by someone else, in another group.
     match result {
-      InitResult::Initialized(id) => Ok(self.new_table(id)),
-      InitResult::ReadExisting(id) => Ok(self.new_table(id)),
+      InitResult::Initialized(id) | InitResult::ReadExisting(id) => {
+        self.new_table(id, &self.get_table_group_by_id(id)?)
+      }
       InitResult::InitErr(err) => Err(Error::ErrorPtr(err)),
     }
   }
 
+  // Counts the records put since the last pass, from the newest key down to the newest one
+  // counted then, or the whole table if it has to be counted from scratch. If the table exceeds
+  // the policy, walks from the oldest key up to the first one which does not, and deletes the
+  // keys before it, updating the rollups derived from the table. The newest key is always kept.
+  #[doc(hidden)]
+  fn enforce_table_retention(&self, id: TableId, policy: RetentionPolicy) -> Result<(), Error> {
+    let table =
+      NormalTable::with_cf(self.inner().clone(), id, ColumnFamily::of_table(self.inner(), id)?);
+    let size = |key: &[u8], value: &[u8]| (TABLE_ID_LEN + 1 + key.len() + value.len()) as u64;
+    let mut cursor = table.new_cursor();
+    cursor.seek_to_first();
+    let oldest_key = match cursor.key() {
+      Some(key) => Bytes::copy_from_slice(key),
+      None => return cursor.status(),
+    };
+    let mut counts =
+      self.retention_counts().take(id).filter(|counts| counts.oldest_key == oldest_key);
+    cursor.seek_to_last();
+    let newest_key = Bytes::copy_from_slice(cursor.key().unwrap_or_default());
+    let (mut bytes, mut records, mut reuses) = (0, 0, 0);
+    while let (Some(key), Some(value)) = (cursor.key(), cursor.value()) {
+      if let Some(counted) = counts.as_ref().filter(|counted| key <= &counted.newest_key[..]) {
+        if key == &counted.newest_key[..] {
+          bytes += counted.bytes;
+          records += counted.records;
+          reuses = counted.reuses + 1;
+          break;
+        }
+        // The newest key counted is gone, so the table is counted from scratch.
+        counts = None;
+      }
+      bytes += size(key, value);
+      records += 1;
+      cursor.prev();
+    }
+    cursor.status()?;
+    let mut kept_key = oldest_key;
+    if policy.is_exceeded(bytes, records) {
+      cursor.seek_to_first();
+      while let (Some(key), Some(value)) = (cursor.key(), cursor.value()) {
+        if key == &newest_key[..] || !policy.is_exceeded(bytes, records) {
+          kept_key = Bytes::copy_from_slice(key);
+          break;
+        }
+        bytes -= size(key, value);
+        records -= 1;
+        cursor.next();
+      }
+      cursor.status()?;
+      let begin_inner_key = build_head_anchor(id);
+      let end_inner_key = build_inner_key(id, &kept_key);
+      let mut batch = WriteBatch::default();
+      batch.delete_range(&begin_inner_key, &end_inner_key);
+      let routing = self.opts().routing();
+      self.rollups().write(self.inner(), batch, &routing, |batch, _| self.write_inner(batch))?;
+      table.cf.compact_range(self.inner(), &begin_inner_key, &end_inner_key);
+    }
+    let counts = TableCounts { oldest_key: kept_key, newest_key, bytes, records, reuses };
+    self.retention_counts().insert(id, counts);
+    Ok(())
+  }
+
+  #[doc(hidden)]
+  #[inline]
+  fn on_table_destroyed(&self, _id: TableId) {}
+
+  // Writes which may touch userland tables go through here, so ttl dbs can see them, and the
+  // ops are routed to the column families of their tables.
+  #[doc(hidden)]
+  #[inline]
+  fn write_inner(&self, batch: WriteBatch) -> Result<(), Error> {
+    let batch = self.opts().routing().apply(self.inner(), batch)?;
+    Ok(self.inner().write(batch)?)
+  }
+
+  // Hands out fresh ids until they run out, then the ids freed by destroyed tables.
   #[doc(hidden)]
   #[inline]
   fn generate_next_table_id(&self) -> Result<TableId, Error> {
-    let current_id = self.last_table_id().fetch_add(1, Ordering::SeqCst) + 1;
-    let current_id2 = u32_to_u8a4(current_id);
-    if current_id2 >= MAX_USERLAND_TABLE_ID {
-      Err(Error::ExceededLimitError {
-        current: current_id,
-        max: u8a4_to_u32(MAX_USERLAND_TABLE_ID),
-      })
-    } else {
-      Ok(current_id2)
+    let max = u8a4_to_u32(MAX_USERLAND_TABLE_ID);
+    let result = self.last_table_id().fetch_update(Ordering::SeqCst, Ordering::SeqCst, |id| {
+      if id + 1 < max {
+        Some(id + 1)
+      } else {
+        None
+      }
+    });
+    match result {
+      Ok(last_table_id) => Ok(u32_to_u8a4(last_table_id + 1)),
+      Err(_) => self.reuse_free_table_id()?.ok_or(Error::ExceededLimitError { current: max, max }),
     }
   }
 
+  // Pops a freed id. Whatever was written under it since its table was destroyed, e.g. through
+  // a handle still held, is cleared before the id is handed out. The catalog lock must be held.
+  #[doc(hidden)]
+  fn reuse_free_table_id(&self) -> Result<Option<TableId>, Error> {
+    let free_id_table = NormalTable::new(self.inner().clone(), FREE_ID_TABLE_ID);
+    let mut cursor = free_id_table.new_cursor();
+    cursor.seek_to_first();
+    if !cursor.is_valid() {
+      cursor.status()?;
+      return Ok(None);
+    }
+    let id = u8s_to_u8a4(cursor.key().unwrap());
+    let mut batch = WriteBatch::default();
+    batch.delete(build_free_id_table_inner_key(id));
+    for inner_key in [
+      build_id_to_ttl_table_inner_key(id),
+      build_id_to_keep_latest_table_inner_key(id),
+      build_id_to_retention_table_inner_key(id),
+      build_id_to_meta_table_inner_key(id),
+      build_id_to_group_table_inner_key(id),
+    ] {
+      batch.delete(inner_key);
+    }
+    batch.delete_range(build_head_anchor(id).as_ref(), build_tail_anchor(id).as_ref());
+    self.write_inner(batch)?;
+    self.on_table_destroyed(id);
+    Ok(Some(id))
+  }
+
+  // The catalog lock must be held.
+  #[doc(hidden)]
+  #[inline]
+  fn register_new_table(&self, name: &str, group: &str) -> Result<TableId, Error> {
+    let id = self.generate_next_table_id()?;
+    let name_to_id_table_inner_key = build_name_to_id_table_inner_key(name);
+    let id_to_name_table_inner_key = build_id_to_name_table_inner_key(id);
+    self.register_table(name_to_id_table_inner_key, id, id_to_name_table_inner_key, name, group)?;
+    Ok(id)
+  }
+
   #[doc(hidden)]
   #[inline]
   fn register_table<K: AsRef<[u8]>>(
     &self, name_to_id_table_inner_key: K, id: TableId, id_to_name_table_inner_key: K, name: &str,
+    group: &str,
   ) -> Result<(), Error> {
+    let now = now();
+    let meta = TableMeta { created_at: now, modified_at: now, ..Default::default() };
     let mut batch = WriteBatch::default();
     batch.put(name_to_id_table_inner_key, id);
     batch.put(id_to_name_table_inner_key, name);
+    batch.put(build_id_to_meta_table_inner_key(id), meta.encode_to_vec());
+    if group != DEFAULT_TABLE_GROUP {
+      batch.put(build_id_to_group_table_inner_key(id), group);
+    }
     Ok(self.inner().write(batch)?)
   }
+
+  #[doc(hidden)]
+  #[inline]
+  fn get_table_meta_by_id(&self, id: TableId) -> Result<TableMeta, Error> {
+    match self.inner().get(build_id_to_meta_table_inner_key(id))? {
+      Some(meta) => Ok(TableMeta::decode(meta.as_slice())?),
+      None => Ok(TableMeta::default()),
+    }
+  }
+
+  #[doc(hidden)]
+  #[inline]
+  fn touch_table_meta(&self, batch: &mut WriteBatch, id: TableId) -> Result<(), Error> {
+    let meta = TableMeta { modified_at: now(), ..self.get_table_meta_by_id(id)? };
+    batch.put(build_id_to_meta_table_inner_key(id), meta.encode_to_vec());
+    Ok(())
+  }
 }
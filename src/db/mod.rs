@@ -1,7 +1,15 @@
+pub mod check;
 pub mod db;
+pub mod namespace;
 pub mod normal_db;
+pub mod open_mode;
+pub mod table_name_iterator;
 pub mod ttl_db;
 
+pub use check::{Finding, RepairMode};
 pub use db::*;
+pub use namespace::Namespace;
 pub use normal_db::*;
+pub use open_mode::*;
+pub use table_name_iterator::*;
 pub use ttl_db::*;
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use super::db::Db;
+use super::open_mode::OpenMode;
+use crate::consts::*;
+use crate::error::Error;
+use crate::retention::RetentionPolicy;
+use crate::table::{TableInfo, TableMeta};
+use crate::types::*;
+use crate::utils::u32_to_u8a4;
+
+/// A view over the tables of a db whose names start with the namespace and a `/`. Names passed
+/// to and returned by the view are relative to the namespace, so a namespace may hold another.
+/// Aliases belong to a namespace as tables do, and only point at tables of the same namespace.
+/// What spans the whole db is left to the db itself, i.e. ttls, rollups, retention enforcement,
+/// checks and repairs, and the write op log.
+pub struct Namespace<'a, D: Db> {
+  db: &'a D,
+  prefix: String,
+}
+
+impl<'a, D: Db> Namespace<'a, D> {
+  #[inline]
+  pub(crate) fn new(db: &'a D, namespace: &str) -> Self {
+    Namespace { db, prefix: build_namespace_prefix(namespace) }
+  }
+
+  #[inline]
+  pub fn name(&self) -> &str {
+    &self.prefix[..self.prefix.len() - NAMESPACE_SEPARATOR.len_utf8()]
+  }
+
+  #[inline]
+  pub fn open_table(&self, name: &str) -> Result<Arc<D::Table>, Error> {
+    self.db.open_table(&self.qualify(name)).map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn open_table_with_mode(&self, name: &str, mode: OpenMode) -> Result<Arc<D::Table>, Error> {
+    self.db.open_table_with_mode(&self.qualify(name), mode).map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn open_table_in_group(&self, name: &str, group: &str) -> Result<Arc<D::Table>, Error> {
+    self.db.open_table_in_group(&self.qualify(name), group).map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn destroy_table(&self, name: &str) -> Result<(), Error> {
+    self.db.destroy_table(&self.qualify(name)).map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn truncate_table(&self, name: &str) -> Result<(), Error> {
+    self.db.truncate_table(&self.qualify(name)).map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn rename_table(&self, old_name: &str, new_name: &str) -> Result<(), Error> {
+    self
+      .db
+      .rename_table(&self.qualify(old_name), &self.qualify(new_name))
+      .map_err(|err| self.unqualify_err(err))
+  }
+
+  /// Same as `Db::copy_table`, but both tables are in the namespace.
+  #[inline]
+  pub fn copy_table(&self, src: &str, dst: &str) -> Result<(), Error> {
+    self
+      .db
+      .copy_table(&self.qualify(src), &self.qualify(dst))
+      .map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn create_alias(&self, alias: &str, target: &str) -> Result<(), Error> {
+    self
+      .db
+      .create_alias(&self.qualify(alias), &self.qualify(target))
+      .map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn swap_alias(&self, alias: &str, new_target: &str) -> Result<(), Error> {
+    self
+      .db
+      .swap_alias(&self.qualify(alias), &self.qualify(new_target))
+      .map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn drop_alias(&self, alias: &str) -> Result<(), Error> {
+    self.db.drop_alias(&self.qualify(alias)).map_err(|err| self.unqualify_err(err))
+  }
+
+  /// Same as `Db::get_alias_infos`, but for the aliases in the namespace only.
+  pub fn get_alias_infos(&self) -> Result<Vec<(String, String)>, Error> {
+    let mut result = Vec::new();
+    for (alias, target) in self.db.get_alias_infos()? {
+      if let (Some(alias), Some(target)) =
+        (alias.strip_prefix(&self.prefix), target.strip_prefix(&self.prefix))
+      {
+        result.push((alias.to_string(), target.to_string()));
+      }
+    }
+    Ok(result)
+  }
+
+  #[inline]
+  pub fn get_table_id_by_name(&self, name: &str) -> Result<Option<TableId>, Error> {
+    self.db.get_table_id_by_name(&self.qualify(name))
+  }
+
+  #[inline]
+  pub fn get_table_group(&self, name: &str) -> Result<Option<String>, Error> {
+    self.db.get_table_group(&self.qualify(name))
+  }
+
+  #[inline]
+  pub fn get_table_meta(&self, name: &str) -> Result<Option<TableMeta>, Error> {
+    self.db.get_table_meta(&self.qualify(name))
+  }
+
+  #[inline]
+  pub fn set_table_meta(&self, name: &str, meta: TableMeta) -> Result<(), Error> {
+    self.db.set_table_meta(&self.qualify(name), meta).map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn set_table_retention(&self, name: &str, policy: RetentionPolicy) -> Result<(), Error> {
+    self.db.set_table_retention(&self.qualify(name), policy).map_err(|err| self.unqualify_err(err))
+  }
+
+  #[inline]
+  pub fn clear_table_retention(&self, name: &str) -> Result<(), Error> {
+    self.db.clear_table_retention(&self.qualify(name))
+  }
+
+  #[inline]
+  pub fn get_table_retention(&self, name: &str) -> Result<Option<RetentionPolicy>, Error> {
+    self.db.get_table_retention(&self.qualify(name))
+  }
+
+  /// Same as `Db::get_table_infos`, but for the tables in the namespace only.
+  #[inline]
+  pub fn get_table_infos(&self) -> Result<Vec<(String, u32)>, Error> {
+    self.list_tables("", None, usize::MAX)
+  }
+
+  /// Same as `Db::get_table_infos_with_meta`, but for the tables in the namespace only.
+  pub fn get_table_infos_with_meta(&self) -> Result<Vec<TableInfo>, Error> {
+    let mut result = Vec::new();
+    for (name, id) in self.get_table_infos()? {
+      let meta = self.db.get_table_meta_by_id(u32_to_u8a4(id))?;
+      result.push(TableInfo { name, id, meta });
+    }
+    Ok(result)
+  }
+
+  /// Same as `Db::list_tables`, but for the tables in the namespace only.
+  pub fn list_tables(
+    &self, prefix: &str, start_after: Option<&str>, limit: usize,
+  ) -> Result<Vec<(String, u32)>, Error> {
+    let start_after = start_after.map(|name| self.qualify(name));
+    self
+      .db
+      .iter_tables(&self.qualify(prefix), start_after.as_deref())
+      .take(limit)
+      .map(|result| result.map(|(name, id)| (name[self.prefix.len()..].to_string(), id)))
+      .collect()
+  }
+
+  #[inline]
+  fn qualify(&self, name: &str) -> String {
+    [&self.prefix, name].concat()
+  }
+
+  #[inline]
+  fn unqualify_err(&self, err: Error) -> Error {
+    let unqualify = |name: String| match name.strip_prefix(&self.prefix) {
+      Some(name) => name.to_string(),
+      None => name,
+    };
+    match err {
+      Error::TableNotFound(name) => Error::TableNotFound(unqualify(name)),
+      Error::TableAlreadyExists(name) => Error::TableAlreadyExists(unqualify(name)),
+      Error::AliasNotFound(alias) => Error::AliasNotFound(unqualify(alias)),
+      Error::TableGroupMismatch { name, current, wanted } => {
+        Error::TableGroupMismatch { name: unqualify(name), current, wanted }
+      }
+      err => err,
+    }
+  }
+}
+
+#[inline]
+pub(crate) fn build_namespace_prefix(namespace: &str) -> String {
+  let mut prefix = String::with_capacity(namespace.len() + NAMESPACE_SEPARATOR.len_utf8());
+  prefix.push_str(namespace);
+  prefix.push(NAMESPACE_SEPARATOR);
+  prefix
+}
@@ -1,6 +1,7 @@
 use std::{
   path::Path,
-  sync::{atomic::AtomicU32, Arc},
+  sync::{atomic::AtomicU32, Arc, Mutex},
+  time::Duration,
 };
 
 use concurrent_initializer::ConcurrentInitializer;
@@ -8,8 +9,11 @@ use quick_cache::{sync::Cache, Weighter};
 use rocksdb::DB as RocksdbDb;
 
 use super::db::Db;
+use crate::column_family::ColumnFamily;
 use crate::error::Error;
 use crate::options::Options;
+use crate::retention::*;
+use crate::rollup::RollupRegistry;
 use crate::table::*;
 use crate::types::*;
 use crate::write_batch::*;
@@ -28,7 +32,12 @@ pub struct NormalDb {
   pub(crate) cache: Cache<String, Arc<NormalTable>, NormalTableWeighter>,
   pub(crate) last_table_id: AtomicU32,
   pub(crate) initializer: ConcurrentInitializer<String, TableId>,
+  pub(crate) catalog_lock: Mutex<()>,
+  pub(crate) live_tables: LiveTables,
+  pub(crate) retention_counts: RetentionCounts,
   pub(crate) opts: Options,
+  pub(crate) rollups: Arc<RollupRegistry>,
+  pub(crate) retention_scheduler: Mutex<Option<RetentionScheduler>>,
 }
 
 impl Db for NormalDb {
@@ -59,29 +68,55 @@ impl Db for NormalDb {
     &self.initializer
   }
 
+  #[inline(always)]
+  fn catalog_lock(&self) -> &Mutex<()> {
+    &self.catalog_lock
+  }
+
+  #[inline(always)]
+  fn live_tables(&self) -> &LiveTables {
+    &self.live_tables
+  }
+
+  #[inline(always)]
+  fn retention_counts(&self) -> &RetentionCounts {
+    &self.retention_counts
+  }
+
   #[inline]
   fn opts(&self) -> &Options {
     &self.opts
   }
 
+  #[inline(always)]
+  fn rollups(&self) -> &Arc<RollupRegistry> {
+    &self.rollups
+  }
+
   ////////////////////////////////////////////////////////////////////////////////
   /// APIs
   ////////////////////////////////////////////////////////////////////////////////
   #[inline]
-  fn new_table(&self, id: TableId) -> Self::Table {
-    NormalTable::new(self.inner.clone(), id)
+  fn new_table(&self, id: TableId, group: &str) -> Result<Self::Table, Error> {
+    let cf = ColumnFamily::find(&self.inner, group)?;
+    let liveness = self.live_tables.get(id);
+    Ok(NormalTable::with_liveness(self.inner.clone(), id, cf, liveness, self.rollups.clone()))
   }
 
   #[inline]
   fn new_write_batch_x(&self) -> Self::WriteBatchX {
-    NormalWriteBatchX::new(self.inner.clone())
+    NormalWriteBatchX::new(self.inner.clone(), self.opts.routing(), self.rollups.clone())
   }
 }
 
 impl NormalDb {
   pub fn open<P: AsRef<Path>>(path: P, opts: &Options) -> Result<Self, Error> {
     let opts = opts.clone();
-    let inner_db = Arc::new(RocksdbDb::open(&opts.inner, path)?);
+    let inner_db = Arc::new(RocksdbDb::open_cf_descriptors(
+      &opts.inner,
+      path,
+      opts.build_column_family_descriptors(),
+    )?);
     Self::try_put_placeholder_to_fix_wal_bug(inner_db.clone())?;
     Self::ensure_ttl_enabled_consistent(inner_db.clone(), false)?;
     Ok(NormalDb {
@@ -93,15 +128,42 @@ impl NormalDb {
       ),
       last_table_id: AtomicU32::new(Self::get_last_table_id(inner_db)?),
       initializer: ConcurrentInitializer::new(),
+      catalog_lock: Mutex::new(()),
+      live_tables: LiveTables::default(),
+      retention_counts: RetentionCounts::default(),
       opts,
+      rollups: Arc::new(RollupRegistry::new(None)),
+      retention_scheduler: Mutex::new(None),
     })
   }
+
+  /// Starts a thread which applies the rules every interval, replacing the one started before.
+  /// For each table, the first rule matching its name applies. The thread is stopped when the
+  /// db is dropped.
+  pub fn start_retention_scheduler(&self, rules: Vec<RetentionRule>, interval: Duration) {
+    let scheduler =
+      RetentionScheduler::spawn(self.inner.clone(), self.rollups.clone(), rules, interval);
+    *self.retention_scheduler.lock().unwrap() = Some(scheduler);
+  }
+
+  #[inline]
+  pub fn stop_retention_scheduler(&self) {
+    self.retention_scheduler.lock().unwrap().take();
+  }
+
+  /// Returns the stats of the latest run of the retention scheduler, if any.
+  #[inline]
+  pub fn last_retention_stats(&self) -> Option<RetentionStats> {
+    self.retention_scheduler.lock().unwrap().as_ref().and_then(|scheduler| scheduler.last_stats())
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
   use crate::consts::*;
+  use crate::cursor::*;
+  use crate::db::{Finding, OpenMode, RepairMode};
   use crate::setup;
   use crate::utils::*;
 
@@ -120,8 +182,15 @@ mod tests {
     let result = table.get(b"k111");
     assert_eq!(std::str::from_utf8(&result.unwrap().unwrap()).unwrap(), "v111");
     db.destroy_table(name).unwrap();
-    let result = table.get(b"k111");
-    assert!(result.unwrap().is_none());
+    assert!(!table.is_alive());
+    assert!(matches!(table.get(b"k111"), Err(Error::TableDestroyed(_))));
+    assert!(matches!(table.put(b"k111", b"v111"), Err(Error::TableDestroyed(_))));
+    let mut cursor = table.new_cursor();
+    cursor.seek_to_first();
+    assert!(!cursor.is_valid());
+    assert!(matches!(cursor.status(), Err(Error::TableDestroyed(_))));
+    assert!(db.inner.get(build_inner_key(table.id(), b"k111")).unwrap().is_none());
+    assert!(db.open_table(name).unwrap().is_alive());
   }
 
   #[test]
@@ -135,6 +204,183 @@ mod tests {
     db.truncate_table(name).unwrap();
     let result = table.get(b"k111");
     assert!(result.unwrap().is_none());
+    assert!(matches!(db.truncate_table("huobi.eth.usdt.1min"), Err(Error::TableNotFound(_))));
+  }
+
+  #[test]
+  fn test_enforce_retention() {
+    use crate::retention::*;
+
+    setup!("normal_db.test_enforce_retention"; db);
+
+    let by_records = db.open_table("by_records").unwrap();
+    let by_bytes = db.open_table("by_bytes").unwrap();
+    for table in [&by_records, &by_bytes] {
+      for key in [b"k1", b"k2", b"k3", b"k4", b"k5"] {
+        table.put(key, b"v").unwrap();
+      }
+    }
+    let mut policy = RetentionPolicy::new();
+    policy.set_max_records(3);
+    db.set_table_retention("by_records", policy).unwrap();
+    assert_eq!(db.get_table_retention("by_records").unwrap(), Some(policy));
+    let mut policy = RetentionPolicy::new();
+    // Each record takes 4 bytes of table id, 1 of separator, 2 of key and 1 of value.
+    policy.set_max_bytes(16);
+    db.set_table_retention("by_bytes", policy).unwrap();
+
+    db.enforce_retention().unwrap();
+
+    assert!(by_records.get(b"k2").unwrap().is_none());
+    assert!(by_records.get(b"k3").unwrap().is_some());
+    assert!(by_bytes.get(b"k3").unwrap().is_none());
+    assert!(by_bytes.get(b"k4").unwrap().is_some());
+
+    db.clear_table_retention("by_records").unwrap();
+    assert!(db.get_table_retention("by_records").unwrap().is_none());
+    db.destroy_table("by_bytes").unwrap();
+    assert!(db.get_table_retention("by_bytes").unwrap().is_none());
+  }
+
+  #[test]
+  fn test_enforce_retention_incrementally() {
+    use crate::retention::*;
+
+    setup!("normal_db.test_enforce_retention_incrementally"; db);
+
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap();
+    for key in [b"k1", b"k2", b"k3", b"k4", b"k5"] {
+      table.put(key, b"v").unwrap();
+    }
+    let mut policy = RetentionPolicy::new();
+    policy.set_max_records(3);
+    db.set_table_retention(name, policy).unwrap();
+    db.enforce_retention().unwrap();
+    assert!(table.get(b"k2").unwrap().is_none());
+    assert!(table.get(b"k3").unwrap().is_some());
+
+    // Only the records put since are counted.
+    for key in [b"k6", b"k7"] {
+      table.put(key, b"v").unwrap();
+    }
+    db.enforce_retention().unwrap();
+    assert!(table.get(b"k4").unwrap().is_none());
+    assert!(table.get(b"k5").unwrap().is_some());
+
+    // The oldest key counted is gone, so the table is counted from scratch.
+    table.delete(b"k5").unwrap();
+    table.put(b"k8", b"v").unwrap();
+    db.enforce_retention().unwrap();
+    assert!(table.get(b"k6").unwrap().is_some());
+
+    // The newest record is kept even if it alone exceeds the cap.
+    let mut policy = RetentionPolicy::new();
+    policy.set_max_bytes(1);
+    db.set_table_retention(name, policy).unwrap();
+    db.enforce_retention().unwrap();
+    assert!(table.get(b"k7").unwrap().is_none());
+    assert!(table.get(b"k8").unwrap().is_some());
+  }
+
+  #[test]
+  fn test_retention_scheduler() {
+    setup!("normal_db.test_retention_scheduler"; db);
+
+    let matched = db.open_table("huobi.btc.usdt.1min").unwrap();
+    let unmatched = db.open_table("huobi.btc.usdt.1day").unwrap();
+    let now = now();
+    let old_key = u64_to_u8a8(now - 31 * 86_400_000);
+    let new_key = u64_to_u8a8(now);
+    for table in [&matched, &unmatched] {
+      table.put(old_key, b"a").unwrap();
+      table.put(new_key, b"b").unwrap();
+    }
+
+    let rule = RetentionRule::new("huobi.*.1min", |now| u64_to_u8a8(now - 30 * 86_400_000));
+    db.start_retention_scheduler(vec![rule], Duration::from_millis(10));
+    let scheduler = db.retention_scheduler.lock().unwrap();
+    scheduler.as_ref().unwrap().background_loop.wait_for_runs(1);
+    drop(scheduler);
+
+    let stats = db.last_retention_stats().unwrap();
+    assert_eq!(stats.tables_matched, 1);
+    assert_eq!(stats.errors, 0);
+    db.stop_retention_scheduler();
+    assert!(db.last_retention_stats().is_none());
+
+    assert!(matched.get(old_key).unwrap().is_none());
+    assert_eq!(&*matched.get(new_key).unwrap().unwrap(), b"b");
+    assert_eq!(&*unmatched.get(old_key).unwrap().unwrap(), b"a");
+  }
+
+  #[test]
+  fn test_table_meta() {
+    setup!("normal_db.test_table_meta"; db);
+
+    let name = "huobi.btc.usdt.1min";
+    assert!(db.get_table_meta(name).unwrap().is_none());
+    assert!(matches!(db.set_table_meta(name, TableMeta::default()), Err(Error::TableNotFound(_))));
+    assert!(db.get_table_id_by_name(name).unwrap().is_none());
+    db.open_table(name).unwrap();
+    let meta = db.get_table_meta(name).unwrap().unwrap();
+    assert!(meta.created_at > 0);
+    assert_eq!(meta.created_at, meta.modified_at);
+
+    let mut new_meta = TableMeta { description: "1min candles".to_string(), ..Default::default() };
+    new_meta.set_coder::<u64>();
+    new_meta.properties.insert("exchange".to_string(), "huobi".to_string());
+    db.set_table_meta(name, new_meta).unwrap();
+    let new_name = "huobi.btc.usdt.1m";
+    db.rename_table(name, new_name).unwrap();
+    let renamed_meta = db.get_table_meta(new_name).unwrap().unwrap();
+    assert_eq!(renamed_meta.created_at, meta.created_at);
+    assert!(renamed_meta.modified_at >= meta.modified_at);
+    assert_eq!(renamed_meta.description, "1min candles");
+    assert_eq!(renamed_meta.coder, "u64");
+    assert_eq!(renamed_meta.properties["exchange"], "huobi");
+
+    let infos = db.get_table_infos_with_meta().unwrap();
+    assert_eq!(infos.len(), 1);
+    assert_eq!(infos[0].name, new_name);
+    assert_eq!(infos[0].meta, renamed_meta);
+
+    let id = db.get_table_id_by_name(new_name).unwrap().unwrap();
+    db.destroy_table(new_name).unwrap();
+    assert!(db.inner.get(build_id_to_meta_table_inner_key(id)).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_list_tables() {
+    setup!("normal_db.test_list_tables"; db);
+
+    for name in [
+      "binance.btc.usdt.1min",
+      "huobi.btc.usdt.1min",
+      "huobi.eth.usdt.1min",
+      "huobi.eth.usdt.1day",
+      "huobi.eth.btc.1min",
+    ] {
+      db.open_table(name).unwrap();
+    }
+
+    let page = db.list_tables("huobi.", None, 2).unwrap();
+    let names: Vec<_> = page.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["huobi.btc.usdt.1min", "huobi.eth.btc.1min"]);
+    let page = db.list_tables("huobi.", Some(&page[1].0), 2).unwrap();
+    let names: Vec<_> = page.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["huobi.eth.usdt.1day", "huobi.eth.usdt.1min"]);
+    assert!(db.list_tables("huobi.", Some("huobi.eth.usdt.1min"), 2).unwrap().is_empty());
+    assert_eq!(db.list_tables("", Some("a"), 10).unwrap().len(), 5);
+
+    let names: Vec<_> =
+      db.iter_tables_matching("huobi.*.usdt.1min", None).map(|result| result.unwrap().0).collect();
+    assert_eq!(names, ["huobi.btc.usdt.1min", "huobi.eth.usdt.1min"]);
+    let page = db.list_tables_matching("*.1min", Some("huobi.btc.usdt.1min"), 10).unwrap();
+    assert_eq!(page.len(), 2);
+
+    db.inner.put(build_name_to_id_table_inner_key("huobi.bad"), [0, 1]).unwrap();
+    assert!(matches!(db.list_tables("huobi.bad", None, 10), Err(Error::InvalidTableId(_))));
   }
 
   #[test]
@@ -159,6 +405,333 @@ mod tests {
     assert_eq!(std::str::from_utf8(&name.unwrap().unwrap()).unwrap(), new_name);
   }
 
+  #[test]
+  fn test_checked_catalog_ops() {
+    setup!("normal_db.test_checked_catalog_ops"; db);
+
+    let name1 = "huobi.btc.usdt.1min";
+    let name2 = "huobi.btc.usdt.5min";
+    db.open_table(name1).unwrap();
+    db.open_table(name2).unwrap();
+    assert!(matches!(db.rename_table(name1, name2), Err(Error::TableAlreadyExists(_))));
+    assert!(matches!(db.rename_table("huobi.eth.usdt.1min", "x"), Err(Error::TableNotFound(_))));
+    assert_eq!(db.get_table_infos().unwrap().len(), 2);
+
+    db.destroy_table(name1).unwrap();
+    assert!(matches!(db.destroy_table(name1), Err(Error::TableNotFound(_))));
+  }
+
+  #[test]
+  fn test_copy_table() {
+    setup!("normal_db.test_copy_table"; db);
+    let src_db = db;
+    setup!("normal_db.test_copy_table.dst"; db);
+
+    let table = src_db.open_table("huobi.btc.usdt.1min").unwrap();
+    for i in 0..(COPY_BATCH_SIZE + 1) as u32 {
+      table.put(u32_to_u8a4(i), b"v").unwrap();
+    }
+    src_db.open_table("huobi.btc.usdt.5min").unwrap().put(b"k", b"v").unwrap();
+
+    src_db.copy_table("huobi.btc.usdt.1min", "huobi.btc.usdt.1min.bak").unwrap();
+    src_db.copy_table_into("huobi.btc.usdt.1min", &*db, "huobi.btc.usdt.1min").unwrap();
+    for copy in [
+      src_db.open_table("huobi.btc.usdt.1min.bak").unwrap(),
+      db.open_table("huobi.btc.usdt.1min").unwrap(),
+    ] {
+      let mut cursor = copy.new_cursor();
+      cursor.seek_to_first();
+      let mut count = 0;
+      while cursor.is_valid() {
+        assert_eq!(cursor.key().unwrap(), u32_to_u8a4(count));
+        count += 1;
+        cursor.next();
+      }
+      assert_eq!(count, COPY_BATCH_SIZE as u32 + 1);
+    }
+    assert!(matches!(src_db.copy_table("huobi.eth.usdt.1min", "x"), Err(Error::TableNotFound(_))));
+  }
+
+  #[test]
+  fn test_alias() {
+    setup!("normal_db.test_alias"; db);
+
+    let v1 = db.open_table("klines.v1").unwrap();
+    v1.put(b"k1", b"v1").unwrap();
+    let v2 = db.open_table("klines.v2").unwrap();
+    v2.put(b"k1", b"v2").unwrap();
+
+    db.create_alias("klines", "klines.v1").unwrap();
+    assert!(matches!(db.create_alias("klines", "klines.v2"), Err(Error::TableAlreadyExists(_))));
+    assert!(matches!(db.create_alias("x", "klines.v3"), Err(Error::TableNotFound(_))));
+    assert!(matches!(db.rename_table("klines.v1", "klines"), Err(Error::TableAlreadyExists(_))));
+    assert_eq!(&*db.open_table("klines").unwrap().get(b"k1").unwrap().unwrap(), b"v1");
+
+    db.swap_alias("klines", "klines.v2").unwrap();
+    assert_eq!(&*db.open_table("klines").unwrap().get(b"k1").unwrap().unwrap(), b"v2");
+    assert_eq!(db.get_alias_infos().unwrap(), [("klines".to_string(), "klines.v2".to_string())]);
+    db.destroy_table("klines.v1").unwrap();
+    assert_eq!(db.get_table_infos().unwrap().len(), 1);
+
+    db.destroy_table("klines.v2").unwrap();
+    assert!(db.get_alias_infos().unwrap().is_empty());
+    assert!(matches!(db.swap_alias("klines", "klines.v1"), Err(Error::AliasNotFound(_))));
+    assert!(matches!(db.drop_alias("klines"), Err(Error::AliasNotFound(_))));
+  }
+
+  #[test]
+  fn test_check_and_repair() {
+    setup!("normal_db.test_check_and_repair"; db);
+
+    let table1 = db.open_table("huobi.btc.usdt.1min").unwrap();
+    table1.put(b"k1", b"v1").unwrap();
+    let table2 = db.open_table("huobi.btc.usdt.5min").unwrap();
+    table2.put(b"k1", b"v1").unwrap();
+    let table3 = db.open_table("huobi.btc.usdt.15min").unwrap();
+    table3.put(b"k1", b"v1").unwrap();
+    assert!(db.check().unwrap().is_empty());
+
+    db.inner.delete(build_id_to_name_table_inner_key(table1.id())).unwrap();
+    db.inner.delete(build_name_to_id_table_inner_key("huobi.btc.usdt.5min")).unwrap();
+    db.inner.put(build_name_to_id_table_inner_key("huobi.eth.usdt.1min"), table3.id()).unwrap();
+    let orphan_id = u32_to_u8a4(u8a4_to_u32(table3.id()) + 1);
+    db.inner.put(build_inner_key(orphan_id, b"k1"), b"v1").unwrap();
+    let orphan_id = u8a4_to_u32(orphan_id);
+    let id1 = u8a4_to_u32(table1.id());
+    let id2 = u8a4_to_u32(table2.id());
+
+    let findings = db.check().unwrap();
+    assert!(
+      findings.contains(&Finding::DanglingName { name: "huobi.btc.usdt.1min".into(), id: id1 })
+    );
+    assert!(findings.contains(&Finding::DanglingId { id: id2, name: "huobi.btc.usdt.5min".into() }));
+    assert!(findings.contains(&Finding::DanglingName {
+      name: "huobi.eth.usdt.1min".into(),
+      id: u8a4_to_u32(table3.id())
+    }));
+    assert!(
+      findings.contains(&Finding::OrphanData { id: id1, group: DEFAULT_TABLE_GROUP.to_string() })
+    );
+    assert!(findings
+      .contains(&Finding::OrphanData { id: orphan_id, group: DEFAULT_TABLE_GROUP.to_string() }));
+    assert!(findings.contains(&Finding::StaleLastTableId {
+      last_table_id: orphan_id - 1,
+      max_table_id: orphan_id
+    }));
+
+    db.repair(RepairMode::Reregister).unwrap();
+    assert!(db.check().unwrap().is_empty());
+    let names: Vec<_> = db.get_table_infos().unwrap().into_iter().map(|(name, _)| name).collect();
+    assert!(names.contains(&format!("orphan.{}", id1)));
+    assert!(names.contains(&format!("orphan.{}", id2)));
+    assert!(names.contains(&format!("orphan.{}", orphan_id)));
+    assert!(names.contains(&"huobi.btc.usdt.15min".to_string()));
+    let table = db.open_table(&format!("orphan.{}", orphan_id)).unwrap();
+    assert_eq!(&*table.get(b"k1").unwrap().unwrap(), b"v1");
+
+    db.inner.delete(build_id_to_name_table_inner_key(table.id())).unwrap();
+    db.repair(RepairMode::DropOrphans).unwrap();
+    assert!(db.check().unwrap().is_empty());
+    assert!(table.get(b"k1").unwrap().is_none());
+    assert_eq!(db.get_table_infos().unwrap().len(), 3);
+  }
+
+  #[test]
+  fn test_namespace() {
+    setup!("normal_db.test_namespace"; db);
+
+    let tenant_a = db.namespace("tenant_a");
+    let tenant_b = db.namespace("tenant_b");
+    tenant_a.open_table("huobi.btc.usdt.1min").unwrap().put(b"k1", b"a").unwrap();
+    tenant_a.open_table("huobi.eth.usdt.1min").unwrap();
+    tenant_b.open_table("huobi.btc.usdt.1min").unwrap().put(b"k1", b"b").unwrap();
+    db.open_table("tenant_a0").unwrap();
+    db.open_table("huobi.btc.usdt.1min").unwrap();
+
+    assert_eq!(tenant_a.name(), "tenant_a");
+    let names: Vec<_> =
+      tenant_a.get_table_infos().unwrap().into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["huobi.btc.usdt.1min", "huobi.eth.usdt.1min"]);
+    assert_eq!(tenant_b.list_tables("huobi.", None, 10).unwrap().len(), 1);
+    assert_eq!(db.get_namespaces().unwrap(), ["tenant_a", "tenant_b"]);
+    let table = db.open_table("tenant_b/huobi.btc.usdt.1min").unwrap();
+    assert_eq!(&*table.get(b"k1").unwrap().unwrap(), b"b");
+
+    tenant_a.rename_table("huobi.eth.usdt.1min", "huobi.eth.usdt.5min").unwrap();
+    assert!(matches!(
+      tenant_a.destroy_table("huobi.eth.usdt.1min"),
+      Err(Error::TableNotFound(name)) if name == "huobi.eth.usdt.1min"
+    ));
+    tenant_b.copy_table("huobi.btc.usdt.1min", "huobi.btc.usdt.copy").unwrap();
+    tenant_b.create_alias("btc", "huobi.btc.usdt.copy").unwrap();
+    db.create_alias("btc", "huobi.btc.usdt.1min").unwrap();
+    assert_eq!(tenant_b.get_alias_infos().unwrap(), [("btc".into(), "huobi.btc.usdt.copy".into())]);
+    assert_eq!(&*tenant_b.open_table("btc").unwrap().get(b"k1").unwrap().unwrap(), b"b");
+    assert!(matches!(
+      tenant_b.swap_alias("eth", "huobi.btc.usdt.1min"),
+      Err(Error::AliasNotFound(alias)) if alias == "eth"
+    ));
+    assert!(tenant_b.get_table_meta("huobi.btc.usdt.copy").unwrap().is_some());
+    let names: Vec<_> =
+      tenant_b.get_table_infos_with_meta().unwrap().into_iter().map(|info| info.name).collect();
+    assert_eq!(names, ["huobi.btc.usdt.1min", "huobi.btc.usdt.copy"]);
+    let table = tenant_a.open_table("huobi.btc.usdt.1min").unwrap();
+    db.drop_namespace("tenant_a").unwrap();
+    assert!(!table.is_alive());
+    assert!(tenant_a.get_table_infos().unwrap().is_empty());
+    assert_eq!(db.get_namespaces().unwrap(), ["tenant_b"]);
+    assert_eq!(db.get_table_infos().unwrap().len(), 4);
+  }
+
+  #[test]
+  fn test_table_groups() {
+    use crate::options::{DBCompressionType, Options};
+    use crate::utils::test_utils::TestContext;
+
+    let mut cold_opts = Options::new();
+    cold_opts.set_compression_type(DBCompressionType::Zstd);
+    let mut opts = Options::new();
+    opts.add_table_group("cold", &cold_opts);
+    let ctx = TestContext::with_opts("normal_db.test_table_groups", &mut opts);
+    let db = ctx.db();
+
+    let hot = db.open_table("huobi.btc.usdt.1min").unwrap();
+    let cold = db.open_table_in_group("huobi.btc.usdt.1day", "cold").unwrap();
+    assert_eq!(hot.group(), DEFAULT_TABLE_GROUP);
+    assert_eq!(cold.group(), "cold");
+    assert_eq!(db.get_table_group("huobi.btc.usdt.1day").unwrap().unwrap(), "cold");
+    assert!(db.get_table_group("huobi.eth.usdt.1day").unwrap().is_none());
+    assert!(matches!(
+      db.open_table_in_group("huobi.eth.usdt.1day", "frozen"),
+      Err(Error::TableGroupNotFound(group)) if group == "frozen"
+    ));
+    assert!(matches!(
+      db.open_table_in_group("huobi.btc.usdt.1min", "cold"),
+      Err(Error::TableGroupMismatch { .. })
+    ));
+    assert_eq!(db.open_table("huobi.btc.usdt.1day").unwrap().group(), "cold");
+
+    cold.put(b"k1", b"a").unwrap();
+    let mut batch = cold.new_write_batch();
+    batch.put(b"k2", b"b");
+    batch.write().unwrap();
+    let mut batch = db.new_write_batch_x();
+    batch.put(hot.id(), b"k1", b"c");
+    batch.put(cold.id(), b"k3", b"d");
+    batch.write().unwrap();
+    assert_eq!(&*cold.get(b"k2").unwrap().unwrap(), b"b");
+    assert_eq!(&*hot.get(b"k1").unwrap().unwrap(), b"c");
+    assert!(db.inner.get(build_inner_key(cold.id(), b"k1")).unwrap().is_none());
+    let mut cursor = cold.new_cursor();
+    cursor.seek_to_first();
+    let mut keys = Vec::new();
+    while cursor.is_valid() {
+      keys.push(cursor.key().unwrap().to_vec());
+      cursor.next();
+    }
+    assert_eq!(keys, [b"k1", b"k2", b"k3"]);
+    assert!(db.check().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_rollup() {
+    use bytes::Bytes;
+
+    use crate::retention::*;
+    use crate::rollup::*;
+
+    fn concat(_: &[u8], entries: &[(Bytes, Bytes)]) -> Vec<u8> {
+      entries.iter().flat_map(|(_, value)| value.to_vec()).collect()
+    }
+
+    setup!("normal_db.test_rollup"; db);
+    let (source_name, target_name, name) =
+      ("huobi.btc.usdt.1min", "huobi.btc.usdt.5min", "huobi.btc.usdt.15min");
+    let source = db.open_table(source_name).unwrap();
+    let target = db.open_table(target_name).unwrap();
+    let get = |table: &NormalTable, secs: u32| table.get(u32_to_u8a4(secs)).unwrap();
+    source.put(u32_to_u8a4(60), b"a").unwrap();
+    let bucketing = Arc::new(SecsKeyBucketing::from_table_name(target_name).unwrap());
+    db.register_rollup(source_name, target_name, bucketing, Arc::new(concat)).unwrap();
+    assert!(get(&target, 0).is_none());
+    db.rebuild_rollup(target_name).unwrap();
+    assert_eq!(get(&target, 0).unwrap(), "a");
+    db.register_rollup(target_name, name, Arc::new(SecsKeyBucketing(900)), Arc::new(concat))
+      .unwrap();
+    db.rebuild_rollup(name).unwrap();
+    let derived = db.open_table(name).unwrap();
+    assert_eq!(get(&derived, 0).unwrap(), "a");
+
+    source.put(u32_to_u8a4(120), b"b").unwrap();
+    assert_eq!(get(&target, 0).unwrap(), "ab");
+    assert_eq!(get(&derived, 0).unwrap(), "ab");
+    let mut batch = source.new_write_batch();
+    batch.put(u32_to_u8a4(300), b"c");
+    batch.put(u32_to_u8a4(360), b"d");
+    batch.delete(u32_to_u8a4(60));
+    batch.write().unwrap();
+    assert_eq!(get(&target, 0).unwrap(), "b");
+    assert_eq!(get(&target, 300).unwrap(), "cd");
+    assert_eq!(get(&derived, 0).unwrap(), "bcd");
+    source.delete(u32_to_u8a4(120)).unwrap();
+    assert!(get(&target, 0).is_none());
+    assert_eq!(get(&derived, 0).unwrap(), "cd");
+    let mut policy = RetentionPolicy::new();
+    policy.set_max_records(1);
+    db.set_table_retention(source_name, policy).unwrap();
+    db.enforce_retention().unwrap();
+    assert_eq!(get(&target, 300).unwrap(), "d");
+    assert_eq!(get(&derived, 0).unwrap(), "d");
+    db.clear_table_retention(source_name).unwrap();
+    let rule = RetentionRule::new(source_name, |_| u32_to_u8a4(400));
+    let stats = RetentionScheduler::run(&db.inner, &db.rollups, &[rule]);
+    assert_eq!(stats.tables_trimmed, 1);
+    assert!(get(&target, 300).is_none());
+    assert!(get(&derived, 0).is_none());
+
+    let bucketing = || Arc::new(SecsKeyBucketing(60));
+    for (from, to) in [(name, source_name), (source_name, source_name)] {
+      assert!(matches!(
+        db.register_rollup(from, to, bucketing(), Arc::new(concat)),
+        Err(Error::InvalidRollup { .. })
+      ));
+    }
+    assert!(matches!(
+      db.register_rollup("huobi.btc.usdt.3min", target_name, bucketing(), Arc::new(concat)),
+      Err(Error::InvalidRollup { .. })
+    ));
+    assert!(matches!(db.unregister_rollup("huobi.btc.usdt.3min"), Err(Error::RollupNotFound(_))));
+
+    db.truncate_table(source_name).unwrap();
+    assert!(get(&target, 300).is_none());
+    assert!(get(&derived, 0).is_none());
+    db.unregister_rollup(name).unwrap();
+    source.put(u32_to_u8a4(0), b"e").unwrap();
+    assert_eq!(get(&target, 0).unwrap(), "e");
+    assert!(get(&derived, 0).is_none());
+    assert!(matches!(db.rebuild_rollup(name), Err(Error::RollupNotFound(_))));
+    db.destroy_table(source_name).unwrap();
+    assert!(matches!(db.rebuild_rollup(target_name), Err(Error::RollupNotFound(_))));
+  }
+
+  #[test]
+  fn test_open_table_with_mode() {
+    setup!("normal_db.test_open_table_with_mode"; db);
+
+    let name = "huobi.btc.usdt.1min";
+    assert!(matches!(
+      db.open_table_with_mode(name, OpenMode::OpenExisting),
+      Err(Error::TableNotFound(_))
+    ));
+    let table = db.open_table_with_mode(name, OpenMode::CreateNew).unwrap();
+    assert!(matches!(
+      db.open_table_with_mode(name, OpenMode::CreateNew),
+      Err(Error::TableAlreadyExists(_))
+    ));
+    assert_eq!(db.open_table_with_mode(name, OpenMode::OpenExisting).unwrap().id(), table.id());
+    assert_eq!(db.open_table_with_mode(name, OpenMode::OpenOrCreate).unwrap().id(), table.id());
+  }
+
   #[test]
   fn test_get_tables() {
     setup!("normal_db.test_get_tables"; db);
@@ -171,14 +744,14 @@ mod tests {
     let id0 = u8a4_to_u32(table0.id);
     let id1 = u8a4_to_u32(table1.id);
     let id2 = u8a4_to_u32(table2.id);
-    let result = db.get_table_infos();
+    let result = db.get_table_infos().unwrap();
     assert_eq!(result, vec![(name0, id0), (name1, id1), (name2, id2)]);
   }
 
   #[test]
   fn test_get_table_id_by_name() {
     setup!("normal_db.test_get_table_id_by_name"; db);
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
     assert_eq!(
       db.get_table_id_by_name("huobi.btc.usdt.1m").unwrap().unwrap(),
@@ -189,7 +762,7 @@ mod tests {
   #[test]
   fn test_get_table_name_by_id() {
     setup!("normal_db.test_get_table_name_by_id"; db);
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
     assert_eq!(
       db.get_table_name_by_id(MIN_USERLAND_TABLE_ID).unwrap().unwrap(),
@@ -197,10 +770,23 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_get_table_name_by_id_invalid() {
+    use crate::error::Error;
+
+    setup!("normal_db.test_get_table_name_by_id_invalid"; db);
+    db.inner.put(build_id_to_name_table_inner_key(MIN_USERLAND_TABLE_ID), [0xff]).unwrap();
+    assert!(matches!(
+      db.get_table_name_by_id(MIN_USERLAND_TABLE_ID),
+      Err(Error::InvalidTableName(_))
+    ));
+    assert!(matches!(db.get_table_infos(), Err(Error::InvalidTableName(_))));
+  }
+
   #[test]
   fn test_get_latest_sn() {
     setup!("normal_db.test_get_latest_sn"; db);
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
     let sn1 = db.get_latest_sn();
     let result = table.put(b"k111", b"v111");
@@ -225,14 +811,15 @@ mod tests {
     let sn0 = db.get_latest_sn();
     assert_eq!(sn0, 2);
 
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap(); // 2 records
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap(); // 3 records
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
-    let table = db.create_table("huobi.btc.usdt.3m").unwrap(); // 2 records
-    db.destroy_table("huobi.btc.usdt.3m").unwrap(); // 3 records
+    let table3m = db.create_table("huobi.btc.usdt.3m", DEFAULT_TABLE_GROUP).unwrap(); // 3 records
+    db.destroy_table("huobi.btc.usdt.3m").unwrap(); // 5 records
+    assert!(matches!(table3m.put(b"k111", b"v111"), Err(Error::TableDestroyed(_))));
 
     let sn1 = db.get_latest_sn();
-    assert_eq!(sn1, 9);
-    assert_eq!(sn0 + 7, sn1);
+    assert_eq!(sn1, 13);
+    assert_eq!(sn0 + 11, sn1);
 
     let result = table.put(b"k111", b"v111"); // 1 record
     assert!(result.is_ok());
@@ -240,7 +827,7 @@ mod tests {
     assert!(result.is_ok());
 
     let sn2 = db.get_latest_sn();
-    assert_eq!(sn2, 11);
+    assert_eq!(sn2, 15);
     assert_eq!(sn1 + 2, sn2);
 
     let mut batch = table.new_write_batch();
@@ -250,7 +837,7 @@ mod tests {
     batch.write().unwrap();
 
     let sn3 = db.get_latest_sn();
-    assert_eq!(sn3, 14);
+    assert_eq!(sn3, 18);
     assert_eq!(sn2 + 3, sn3);
 
     let iter = db.get_write_op_batches_since(0).unwrap();
@@ -258,15 +845,29 @@ mod tests {
     for ub in iter {
       result.push(ub.unwrap());
     }
-    assert_eq!(format!("{:?}", result), "[WriteOpBatch { sn: 2, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\0\\x01\\0\\x01\", inner_value: b\"\\0\" })) }] }, WriteOpBatch { sn: 3, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x01\\x01huobi.btc.usdt.1m\", inner_value: b\"\\0\\0\\x04\\0\" })) }, OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x02\\x01\\0\\0\\x04\\0\", inner_value: b\"huobi.btc.usdt.1m\" })) }] }, WriteOpBatch { sn: 5, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x01\\x01huobi.btc.usdt.3m\", inner_value: b\"\\0\\0\\x04\\x01\" })) }, OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x02\\x01\\0\\0\\x04\\x01\", inner_value: b\"huobi.btc.usdt.3m\" })) }] }, WriteOpBatch { sn: 7, write_ops: [OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\0\\x01\\x01huobi.btc.usdt.3m\" })) }, OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\0\\x02\\x01\\0\\0\\x04\\x01\" })) }, OptionalWriteOp { inner: Some(DeleteRangeOp(DeleteRangeOp { begin_inner_key: b\"\\0\\0\\x04\\x01\\0\", end_inner_key: b\"\\0\\0\\x04\\x01\\x02\" })) }] }, WriteOpBatch { sn: 10, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\x04\\x01\\x01k111\", inner_value: b\"v111\" })) }] }, WriteOpBatch { sn: 11, write_ops: [OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\x04\\x01\\x01k111\" })) }] }, WriteOpBatch { sn: 12, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\x04\\x01\\x01k112\", inner_value: b\"v112\" })) }, OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\x04\\x01\\x01k111\" })) }, OptionalWriteOp { inner: Some(DeleteRangeOp(DeleteRangeOp { begin_inner_key: b\"\\0\\0\\x04\\x01\\x01k111\", end_inner_key: b\"\\0\\0\\x04\\x01\\x01k112\" })) }] }]");
+    mask_meta_values(&mut result);
+    assert_eq!(format!("{:?}", result), "[WriteOpBatch { sn: 2, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\0\\x01\\0\\x01\", inner_value: b\"\\0\" })), cf_id: 0 }] }, WriteOpBatch { sn: 3, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x01\\x01huobi.btc.usdt.1m\", inner_value: b\"\\0\\0\\x04\\0\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x02\\x01\\0\\0\\x04\\0\", inner_value: b\"huobi.btc.usdt.1m\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x06\\x01\\0\\0\\x04\\0\", inner_value: b\"meta\" })), cf_id: 0 }] }, WriteOpBatch { sn: 6, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x01\\x01huobi.btc.usdt.3m\", inner_value: b\"\\0\\0\\x04\\x01\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x02\\x01\\0\\0\\x04\\x01\", inner_value: b\"huobi.btc.usdt.3m\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x06\\x01\\0\\0\\x04\\x01\", inner_value: b\"meta\" })), cf_id: 0 }] }, WriteOpBatch { sn: 9, write_ops: [OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\0\\x01\\x01huobi.btc.usdt.3m\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\0\\x02\\x01\\0\\0\\x04\\x01\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\0\\x06\\x01\\0\\0\\x04\\x01\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(DeleteRangeOp(DeleteRangeOp { begin_inner_key: b\"\\0\\0\\x04\\x01\\0\", end_inner_key: b\"\\0\\0\\x04\\x01\\x02\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\0\\x07\\x01\\0\\0\\x04\\x01\", inner_value: b\"\" })), cf_id: 0 }] }, WriteOpBatch { sn: 14, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\x04\\0\\x01k111\", inner_value: b\"v111\" })), cf_id: 0 }] }, WriteOpBatch { sn: 15, write_ops: [OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\x04\\0\\x01k111\" })), cf_id: 0 }] }, WriteOpBatch { sn: 16, write_ops: [OptionalWriteOp { inner: Some(PutOp(PutOp { inner_key: b\"\\0\\0\\x04\\0\\x01k112\", inner_value: b\"v112\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(DeleteOp(DeleteOp { inner_key: b\"\\0\\0\\x04\\0\\x01k111\" })), cf_id: 0 }, OptionalWriteOp { inner: Some(DeleteRangeOp(DeleteRangeOp { begin_inner_key: b\"\\0\\0\\x04\\0\\x01k111\", end_inner_key: b\"\\0\\0\\x04\\0\\x01k112\" })), cf_id: 0 }] }]");
+  }
+
+  // Metadata records hold the creation time, so their values are replaced to compare the ops.
+  fn mask_meta_values(write_op_batches: &mut [crate::write_op::WriteOpBatch]) {
+    use crate::write_op::WriteOp;
+
+    for write_op in write_op_batches.iter_mut().flat_map(|batch| batch.write_ops.iter_mut()) {
+      if let Some(WriteOp::PutOp(put_op)) = &mut write_op.inner {
+        if put_op.inner_key.starts_with(&ID_TO_META_TABLE_ID) {
+          put_op.inner_value = bytes::Bytes::from_static(b"meta");
+        }
+      }
+    }
   }
 
   #[test]
   fn test_create_table() {
     setup!("normal_db.test_create_table"; db);
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
-    let table = db.create_table("huobi.btc.usdt.5m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.5m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, [0, 0, 4, 1]);
   }
 
@@ -277,6 +878,30 @@ mod tests {
     assert_eq!(id, MIN_USERLAND_TABLE_ID);
   }
 
+  #[test]
+  fn test_reuse_table_id() {
+    setup!("normal_db.test_reuse_table_id"; db);
+    let max = u8a4_to_u32(MAX_USERLAND_TABLE_ID);
+    db.last_table_id.store(max - 3, std::sync::atomic::Ordering::SeqCst);
+    let table1 = db.open_table("huobi.btc.usdt.1min").unwrap();
+    db.open_table("huobi.eth.usdt.1min").unwrap();
+    assert!(matches!(db.open_table("huobi.xrp.usdt.1min"), Err(Error::ExceededLimitError { .. })));
+
+    table1.put(b"k111", b"v111").unwrap();
+    let mut policy = RetentionPolicy::new();
+    policy.set_max_records(1);
+    db.set_table_retention("huobi.btc.usdt.1min", policy).unwrap();
+    db.destroy_table("huobi.btc.usdt.1min").unwrap();
+    db.inner.put(build_inner_key(table1.id(), b"k222"), b"v222").unwrap();
+
+    let table3 = db.open_table("huobi.xrp.usdt.1min").unwrap();
+    assert_eq!(table3.id(), table1.id());
+    assert!(table3.get(b"k111").unwrap().is_none());
+    assert!(table3.get(b"k222").unwrap().is_none());
+    assert!(db.get_table_retention("huobi.xrp.usdt.1min").unwrap().is_none());
+    assert!(db.open_table("huobi.ada.usdt.1min").is_err());
+  }
+
   #[test]
   fn test_register_table() {
     setup!("normal_db.test_register_table"; db);
@@ -290,6 +915,7 @@ mod tests {
       MIN_USERLAND_TABLE_ID,
       &id_to_name_table_inner_key,
       name,
+      DEFAULT_TABLE_GROUP,
     );
     assert!(result.is_ok());
 
@@ -0,0 +1,8 @@
+/// How `Db::open_table_with_mode` treats an existing table, or a missing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenMode {
+  OpenExisting,
+  CreateNew,
+  #[default]
+  OpenOrCreate,
+}
@@ -0,0 +1,82 @@
+use bytes::Bytes;
+use rocksdb::DBRawIterator;
+
+use crate::error::Error;
+use crate::types::*;
+use crate::utils::*;
+
+/// Iterates the tables of a db in name order, through the name to id index. Yields the names and
+/// ids of the tables whose names start with the prefix and, if any, match the glob pattern.
+pub struct TableNameIterator<'a> {
+  inner: DBRawIterator<'a>,
+  prefix: Bytes,
+  pattern: Option<String>,
+  done: bool,
+}
+
+impl<'a> Iterator for TableNameIterator<'a> {
+  type Item = Result<(String, u32), Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while !self.done {
+      if !self.inner.valid() {
+        self.done = true;
+        return self.inner.status().err().map(|err| Err(err.into()));
+      }
+      let inner_key = self.inner.key().unwrap();
+      if !inner_key.starts_with(&self.prefix) {
+        self.done = true;
+        return None;
+      }
+      let result = Self::parse(inner_key, self.inner.value().unwrap());
+      self.inner.next();
+      match result {
+        Ok((name, _)) if !self.matches(&name) => continue,
+        result => return Some(result),
+      }
+    }
+    None
+  }
+}
+
+impl<'a> TableNameIterator<'a> {
+  pub(crate) fn new(
+    mut inner: DBRawIterator<'a>, prefix: &str, pattern: Option<&str>, start_after: Option<&str>,
+  ) -> Self {
+    let prefix = build_name_to_id_table_inner_key(prefix);
+    match start_after.map(build_name_to_id_table_inner_key) {
+      Some(start_after) if start_after >= prefix => {
+        inner.seek(&start_after);
+        if inner.key() == Some(&start_after[..]) {
+          inner.next();
+        }
+      }
+      _ => inner.seek(&prefix),
+    }
+    TableNameIterator { inner, prefix, pattern: pattern.map(str::to_string), done: false }
+  }
+
+  // Moves on to the first name at or after the given one.
+  #[inline]
+  pub(crate) fn seek(&mut self, name: &str) {
+    self.inner.seek(build_name_to_id_table_inner_key(name));
+    self.done = false;
+  }
+
+  #[inline]
+  fn matches(&self, name: &str) -> bool {
+    match &self.pattern {
+      Some(pattern) => matches_glob(pattern, name),
+      None => true,
+    }
+  }
+
+  #[inline]
+  fn parse(inner_key: &[u8], inner_value: &[u8]) -> Result<(String, u32), Error> {
+    let name = std::str::from_utf8(extract_key(inner_key))?;
+    if inner_value.len() != std::mem::size_of::<TableId>() {
+      return Err(Error::InvalidTableId(inner_value.to_vec()));
+    }
+    Ok((name.to_string(), u8s_to_u32(inner_value)))
+  }
+}
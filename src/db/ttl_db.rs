@@ -1,18 +1,25 @@
 use std::{
   path::Path,
-  sync::{atomic::AtomicU32, Arc},
+  sync::{atomic::AtomicU32, Arc, Mutex},
 };
 
 use concurrent_initializer::ConcurrentInitializer;
 use quick_cache::{sync::Cache, Weighter};
-use rocksdb::DB as RocksdbDb;
+use rocksdb::{ReadOptions, WriteBatch as RocksdbWriteBatch, DB as RocksdbDb};
 
 use super::db::Db;
+use crate::column_family::*;
 use crate::compact_filter::CompactionFilterFactoryImpl;
+use crate::consts::*;
 use crate::error::Error;
+use crate::key_timestamp_extractor::KeyTimestampExtractor;
 use crate::options::Options;
+use crate::retention::RetentionCounts;
+use crate::rollup::RollupRegistry;
 use crate::table::*;
+use crate::ttl_registry::TtlRegistry;
 use crate::types::*;
+use crate::utils::*;
 use crate::write_batch::*;
 
 #[derive(Clone)]
@@ -30,7 +37,12 @@ pub struct TtlDb {
   pub(crate) cache: Cache<String, Arc<TtlTable>, TtlTableWeighter>,
   pub(crate) last_table_id: AtomicU32,
   pub(crate) initializer: ConcurrentInitializer<String, TableId>,
+  pub(crate) catalog_lock: Mutex<()>,
+  pub(crate) live_tables: LiveTables,
+  pub(crate) retention_counts: RetentionCounts,
   pub(crate) opts: Options,
+  pub(crate) ttl_registry: Arc<TtlRegistry>,
+  pub(crate) rollups: Arc<RollupRegistry>,
 }
 
 impl Db for TtlDb {
@@ -61,33 +73,90 @@ impl Db for TtlDb {
     &self.initializer
   }
 
+  #[inline]
+  fn catalog_lock(&self) -> &Mutex<()> {
+    &self.catalog_lock
+  }
+
+  #[inline]
+  fn live_tables(&self) -> &LiveTables {
+    &self.live_tables
+  }
+
+  #[inline]
+  fn retention_counts(&self) -> &RetentionCounts {
+    &self.retention_counts
+  }
+
   #[inline]
   fn opts(&self) -> &Options {
     &self.opts
   }
 
+  #[inline]
+  fn rollups(&self) -> &Arc<RollupRegistry> {
+    &self.rollups
+  }
+
   ////////////////////////////////////////////////////////////////////////////////
   /// APIs
   ////////////////////////////////////////////////////////////////////////////////
   #[inline]
-  fn new_table(&self, id: TableId) -> Self::Table {
-    TtlTable::new(self.inner.clone(), id)
+  fn new_table(&self, id: TableId, group: &str) -> Result<Self::Table, Error> {
+    let cf = ColumnFamily::find(&self.inner, group)?;
+    let liveness = self.live_tables.get(id);
+    let ttl_registry = self.ttl_registry.clone();
+    Ok(TtlTable::new(self.inner.clone(), id, cf, ttl_registry, liveness, self.rollups.clone()))
   }
 
   #[inline]
   fn new_write_batch_x(&self) -> Self::WriteBatchX {
-    TtlWriteBatchX::new(self.inner.clone())
+    TtlWriteBatchX::with_routing(
+      self.inner.clone(),
+      self.opts.routing(),
+      self.ttl_registry.clone(),
+      self.rollups.clone(),
+    )
+  }
+
+  ////////////////////////////////////////////////////////////////////////////////
+  /// Private functions
+  ////////////////////////////////////////////////////////////////////////////////
+  #[doc(hidden)]
+  #[inline]
+  fn on_table_destroyed(&self, id: TableId) {
+    self.ttl_registry.remove_table_ttl(id);
+    self.ttl_registry.remove_table_keep_latest(id);
+    self.ttl_registry.remove_table_key_timestamp_extractor(id);
+    self.ttl_registry.remove_floor(id);
+  }
+
+  #[doc(hidden)]
+  #[inline]
+  fn write_inner(&self, batch: RocksdbWriteBatch) -> Result<(), Error> {
+    self.ttl_registry.write(&self.inner, batch, &self.opts.routing())
   }
 }
 
 impl TtlDb {
   pub fn open<P: AsRef<Path>>(path: P, ttl: u32, opts: &Options) -> Result<Self, Error> {
     let mut opts = opts.clone();
-    opts.set_compaction_filter_factory(CompactionFilterFactoryImpl::new(&path, ttl, opts.clone()));
-    let inner_db = Arc::new(RocksdbDb::open(&opts.inner, &path)?);
+    let ttl_registry = Arc::new(TtlRegistry::new(ttl, opts.keep_latest));
+    opts.set_compaction_filter_factory(|| CompactionFilterFactoryImpl::new(ttl_registry.clone()));
+    let inner_db = Arc::new(RocksdbDb::open_cf_descriptors(
+      &opts.inner,
+      &path,
+      opts.build_column_family_descriptors(),
+    )?);
     Self::try_put_placeholder_to_fix_wal_bug(inner_db.clone())?;
     Self::ensure_ttl_enabled_consistent(inner_db.clone(), true)?;
-    Ok(TtlDb {
+    Self::ensure_value_format_upgraded(inner_db.clone(), &opts.list_table_groups())?;
+    ttl_registry.load(inner_db.clone())?;
+    if let Some(extractor) = opts.key_timestamp_extractor.clone() {
+      ttl_registry.set_key_timestamp_extractor(extractor);
+    }
+    let table_key_timestamp_extractors = opts.table_key_timestamp_extractors.clone();
+    let db = TtlDb {
       inner: inner_db.clone(),
       cache: Cache::with_weighter(
         opts.cache_capacity,
@@ -96,8 +165,191 @@ impl TtlDb {
       ),
       last_table_id: AtomicU32::new(Self::get_last_table_id(inner_db)?),
       initializer: ConcurrentInitializer::new(),
+      catalog_lock: Mutex::new(()),
+      live_tables: LiveTables::default(),
+      retention_counts: RetentionCounts::default(),
       opts,
-    })
+      rollups: Arc::new(RollupRegistry::new(Some(ttl_registry.clone()))),
+      ttl_registry,
+    };
+    // Compactions expire nothing until the extractors the db was opened with are registered.
+    for (name, extractor) in table_key_timestamp_extractors {
+      db.open_table(&name)?;
+      db.set_table_key_timestamp_extractor(&name, extractor)?;
+    }
+    db.ttl_registry.set_ready();
+    Ok(db)
+  }
+
+  #[inline]
+  pub fn ttl(&self) -> u32 {
+    self.ttl_registry.ttl()
+  }
+
+  /// Overrides the db-wide ttl for the given table. Returns `Error::TableNotFound` if there is
+  /// no such table.
+  pub fn set_table_ttl(&self, name: &str, ttl: u32) -> Result<(), Error> {
+    let _guard = self.catalog_lock.lock().unwrap();
+    let id = match self.get_table_id_by_name(name)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(name.to_string())),
+    };
+    self.inner.put(build_id_to_ttl_table_inner_key(id), u32_to_u8a4(ttl))?;
+    self.ttl_registry.set_table_ttl(id, ttl);
+    Ok(())
+  }
+
+  /// Drops the table's own ttl, so the db-wide ttl applies again.
+  pub fn clear_table_ttl(&self, name: &str) -> Result<(), Error> {
+    if let Some(id) = self.get_table_id_by_name(name)? {
+      self.inner.delete(build_id_to_ttl_table_inner_key(id))?;
+      self.ttl_registry.remove_table_ttl(id);
+    }
+    Ok(())
+  }
+
+  /// Returns the ttl in effect for the given table, or `None` if there is no such table.
+  #[inline]
+  pub fn get_table_ttl(&self, name: &str) -> Result<Option<u32>, Error> {
+    Ok(self.get_table_id_by_name(name)?.map(|id| self.ttl_registry.get_ttl(id)))
+  }
+
+  #[inline]
+  pub fn keep_latest(&self) -> u32 {
+    self.ttl_registry.keep_latest()
+  }
+
+  /// Overrides the db-wide number of newest records which never expire for the given table.
+  /// Returns `Error::TableNotFound` if there is no such table.
+  pub fn set_table_keep_latest(&self, name: &str, keep_latest: u32) -> Result<(), Error> {
+    // Held until the floor is refreshed, so none is rebuilt for a destroyed table.
+    let _guard = self.catalog_lock.lock().unwrap();
+    let id = match self.get_table_id_by_name(name)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(name.to_string())),
+    };
+    self.inner.put(build_id_to_keep_latest_table_inner_key(id), u32_to_u8a4(keep_latest))?;
+    self.ttl_registry.set_table_keep_latest(id, keep_latest);
+    self.ttl_registry.refresh_floor(&self.inner, id)
+  }
+
+  /// Drops the table's own number of newest records to keep, so the db-wide one applies again.
+  pub fn clear_table_keep_latest(&self, name: &str) -> Result<(), Error> {
+    let _guard = self.catalog_lock.lock().unwrap();
+    if let Some(id) = self.get_table_id_by_name(name)? {
+      self.inner.delete(build_id_to_keep_latest_table_inner_key(id))?;
+      self.ttl_registry.remove_table_keep_latest(id);
+      self.ttl_registry.refresh_floor(&self.inner, id)?;
+    }
+    Ok(())
+  }
+
+  /// Returns the number of newest records kept for the given table, or `None` if there is no
+  /// such table.
+  #[inline]
+  pub fn get_table_keep_latest(&self, name: &str) -> Result<Option<u32>, Error> {
+    Ok(self.get_table_id_by_name(name)?.map(|id| self.ttl_registry.get_keep_latest(id)))
+  }
+
+  /// Derives the timestamps of the values put from now on from their keys, in every table
+  /// without an extractor of its own. Extractors are not persisted: until they are registered
+  /// again after a reopen, the values put with them never expire, so prefer
+  /// `Options::set_key_timestamp_extractor`, which registers them whenever the db is opened.
+  #[inline]
+  pub fn set_key_timestamp_extractor(&self, extractor: Arc<dyn KeyTimestampExtractor>) {
+    self.ttl_registry.set_key_timestamp_extractor(extractor);
+  }
+
+  /// Same as `set_key_timestamp_extractor`, but for the given table only, until it is destroyed.
+  /// Returns `Error::TableNotFound` if there is no such table. See
+  /// `Options::set_table_key_timestamp_extractor`.
+  pub fn set_table_key_timestamp_extractor(
+    &self, name: &str, extractor: Arc<dyn KeyTimestampExtractor>,
+  ) -> Result<(), Error> {
+    let _guard = self.catalog_lock.lock().unwrap();
+    let id = match self.get_table_id_by_name(name)? {
+      Some(id) => id,
+      None => return Err(Error::TableNotFound(name.to_string())),
+    };
+    self.ttl_registry.set_table_key_timestamp_extractor(id, extractor);
+    Ok(())
+  }
+
+  #[inline]
+  pub fn clear_table_key_timestamp_extractor(&self, name: &str) -> Result<(), Error> {
+    if let Some(id) = self.get_table_id_by_name(name)? {
+      self.ttl_registry.remove_table_key_timestamp_extractor(id);
+    }
+    Ok(())
+  }
+
+  // Values written before the tagged format are prefixed with a bare timestamp in seconds. They
+  // are rewritten with a millisecond header, group by group, in batches which also record the
+  // group and the last key rewritten in it, so an interrupted upgrade resumes where it stopped
+  // the next time the db is opened. Tagged headers in seconds are left as they are, since they
+  // are told apart from the newer ones by their kind. Values too short to hold a timestamp are
+  // left as they are too, and counted in the log.
+  pub(crate) fn ensure_value_format_upgraded(
+    inner_db: Arc<RocksdbDb>, groups: &[&str],
+  ) -> Result<(), Error> {
+    let value_format_item_inner_key = build_info_table_inner_key(VALUE_FORMAT_ITEM_ID);
+    if let Some(value_format) = inner_db.get(&value_format_item_inner_key)? {
+      if value_format.first().copied().unwrap_or_default() < MILLIS_VALUE_FORMAT {
+        inner_db.put(&value_format_item_inner_key, [MILLIS_VALUE_FORMAT])?;
+      }
+      return Ok(());
+    }
+    let progress_item_inner_key = build_info_table_inner_key(VALUE_FORMAT_UPGRADE_PROGRESS_ITEM_ID);
+    let progress = inner_db.get(&progress_item_inner_key)?;
+    let resumed = progress.as_deref().and_then(decode_group_progress);
+    let first_group = match resumed {
+      Some((group, _)) => match groups.iter().position(|candidate| *candidate == group) {
+        Some(position) => position,
+        None => return Err(Error::TableGroupNotFound(group.to_string())),
+      },
+      None => 0,
+    };
+    let mut batch = RocksdbWriteBatch::default();
+    let mut skipped = 0;
+    for (position, group) in groups.iter().enumerate().skip(first_group) {
+      let cf = ColumnFamily::find(&inner_db, group)?;
+      let mut opts = ReadOptions::default();
+      opts.set_total_order_seek(true);
+      let mut iter = cf.raw_iterator_opt(&inner_db, opts);
+      match resumed {
+        Some((_, last_inner_key)) if position == first_group => {
+          iter.seek(last_inner_key);
+          if iter.key() == Some(last_inner_key) {
+            iter.next();
+          }
+        }
+        _ => iter.seek(MIN_USERLAND_TABLE_ID),
+      }
+      while iter.valid() {
+        let inner_key = iter.key().unwrap();
+        let inner_value = iter.value().unwrap();
+        if inner_value.len() >= SECS_TIMESTAMP_LEN {
+          let timestamp = u8s_to_u32(inner_value) as u64 * 1000;
+          let value =
+            build_timestamped_value(u64_to_u8a8(timestamp), &inner_value[SECS_TIMESTAMP_LEN..]);
+          cf.put_into(&inner_db, &mut batch, inner_key, &value[..]);
+        } else {
+          skipped += 1;
+        }
+        if batch.len() >= VALUE_FORMAT_UPGRADE_BATCH_SIZE {
+          batch.put(&progress_item_inner_key, encode_group_progress(group, inner_key));
+          inner_db.write(std::mem::take(&mut batch))?;
+        }
+        iter.next();
+      }
+      iter.status()?;
+    }
+    if skipped > 0 {
+      log::warn!("Left values too short to be upgraded as they are: count: {}", skipped);
+    }
+    batch.delete(&progress_item_inner_key);
+    batch.put(&value_format_item_inner_key, [MILLIS_VALUE_FORMAT]);
+    Ok(inner_db.write(batch)?)
   }
 }
 
@@ -105,9 +357,7 @@ impl TtlDb {
 mod tests {
 
   use super::*;
-  use crate::consts::*;
   use crate::setup_with_ttl;
-  use crate::utils::*;
 
   #[test]
   fn test_new_table() {
@@ -117,6 +367,8 @@ mod tests {
 
   #[test]
   fn test_destroy_table() {
+    use crate::cursor::*;
+
     setup_with_ttl!("ttl_db.test_destroy_table"; 3; db);
     let name = "huobi.btc.usdt.1min";
     let table = db.open_table(name).unwrap();
@@ -124,8 +376,15 @@ mod tests {
     let result = table.get(b"k111");
     assert_eq!(std::str::from_utf8(&result.unwrap().unwrap()).unwrap(), "v111");
     db.destroy_table(name).unwrap();
-    let result = table.get(b"k111");
-    assert!(result.unwrap().is_none());
+    assert!(!table.is_alive());
+    assert!(matches!(table.get(b"k111"), Err(Error::TableDestroyed(_))));
+    assert!(matches!(table.put(b"k111", b"v111"), Err(Error::TableDestroyed(_))));
+    let mut cursor = table.new_cursor();
+    cursor.seek_to_first();
+    assert!(!cursor.is_valid());
+    assert!(matches!(cursor.status(), Err(Error::TableDestroyed(_))));
+    assert!(db.inner.get(build_inner_key(table.id(), b"k111")).unwrap().is_none());
+    assert!(db.open_table(name).unwrap().is_alive());
   }
 
   #[test]
@@ -141,6 +400,52 @@ mod tests {
     assert!(result.unwrap().is_none());
   }
 
+  #[test]
+  fn test_copy_table() {
+    use crate::cursor::*;
+
+    setup_with_ttl!("ttl_db.test_copy_table"; 3; db);
+    let table = db.open_table("huobi.btc.usdt.1min").unwrap();
+    let ts = table.put_timestamped(b"k111", b"v111").unwrap();
+    table.put_with_ttl(b"k222", b"v222", 60).unwrap();
+    db.copy_table("huobi.btc.usdt.1min", "huobi.btc.usdt.1min.bak").unwrap();
+
+    let copy = db.open_table("huobi.btc.usdt.1min.bak").unwrap();
+    let mut cursor = copy.new_cursor();
+    cursor.seek_to_first();
+    assert_eq!(cursor.timestamped_value().unwrap(), (ts, &b"v111"[..]));
+    cursor.next();
+    assert_eq!(cursor.value().unwrap(), b"v222");
+    assert!(matches!(
+      db.copy_table("huobi.btc.usdt.1min", "huobi.btc.usdt.1min.bak"),
+      Err(Error::TableAlreadyExists(_))
+    ));
+  }
+
+  #[test]
+  fn test_rollup() {
+    use bytes::Bytes;
+
+    use crate::rollup::*;
+
+    fn concat(_: &[u8], entries: &[(Bytes, Bytes)]) -> Vec<u8> {
+      entries.iter().flat_map(|(_, value)| value.to_vec()).collect()
+    }
+
+    setup_with_ttl!("ttl_db.test_rollup"; 3; db);
+    let source = db.open_table("huobi.btc.usdt.1min").unwrap();
+    let bucketing = Arc::new(SecsKeyBucketing(300));
+    db.register_rollup("huobi.btc.usdt.1min", "huobi.btc.usdt.5min", bucketing, Arc::new(concat))
+      .unwrap();
+    source.put(u32_to_u8a4(0), b"a").unwrap();
+    source.put_timestamped(u32_to_u8a4(60), b"b").unwrap();
+    source.put_with_ttl(u32_to_u8a4(120), b"c", 60).unwrap();
+    let target = db.open_table("huobi.btc.usdt.5min").unwrap();
+    assert_eq!(target.get(u32_to_u8a4(0)).unwrap().unwrap(), "abc");
+    source.delete(u32_to_u8a4(60)).unwrap();
+    assert_eq!(target.get(u32_to_u8a4(0)).unwrap().unwrap(), "ac");
+  }
+
   #[test]
   fn test_rename_table() {
     setup_with_ttl!("ttl_db.test_rename_table"; 3; db);
@@ -175,14 +480,14 @@ mod tests {
     let id0 = u8a4_to_u32(table0.id);
     let id1 = u8a4_to_u32(table1.id);
     let id2 = u8a4_to_u32(table2.id);
-    let result = db.get_table_infos();
+    let result = db.get_table_infos().unwrap();
     assert_eq!(result, vec![(name0, id0), (name1, id1), (name2, id2)]);
   }
 
   #[test]
   fn test_get_table_id_by_name() {
     setup_with_ttl!("ttl_db.test_get_table_id_by_name"; 3; db);
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
     assert_eq!(
       db.get_table_id_by_name("huobi.btc.usdt.1m").unwrap().unwrap(),
@@ -193,7 +498,7 @@ mod tests {
   #[test]
   fn test_get_table_name_by_id() {
     setup_with_ttl!("ttl_db.test_get_table_name_by_id"; 3; db);
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
     assert_eq!(
       db.get_table_name_by_id(MIN_USERLAND_TABLE_ID).unwrap().unwrap(),
@@ -204,7 +509,7 @@ mod tests {
   #[test]
   fn test_get_latest_sn() {
     setup_with_ttl!("ttl_db.test_get_latest_sn"; 3; db);
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
     let sn1 = db.get_latest_sn();
     let result = table.put(b"k111", b"v111");
@@ -225,9 +530,9 @@ mod tests {
   #[test]
   fn test_create_table() {
     setup_with_ttl!("ttl_db.test_create_table"; 3; db);
-    let table = db.create_table("huobi.btc.usdt.1m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.1m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, MIN_USERLAND_TABLE_ID);
-    let table = db.create_table("huobi.btc.usdt.5m").unwrap();
+    let table = db.create_table("huobi.btc.usdt.5m", DEFAULT_TABLE_GROUP).unwrap();
     assert_eq!(table.id, [0, 0, 4, 1]);
   }
 
@@ -251,6 +556,7 @@ mod tests {
       MIN_USERLAND_TABLE_ID,
       &id_to_name_table_inner_key,
       name,
+      DEFAULT_TABLE_GROUP,
     );
     assert!(result.is_ok());
 
@@ -261,6 +567,301 @@ mod tests {
     assert_eq!(std::str::from_utf8(&name.unwrap().unwrap()).unwrap(), "huobi.btc.usdt.1m");
   }
 
+  #[test]
+  fn test_table_ttl() {
+    setup_with_ttl!("ttl_db.test_table_ttl"; 3; db);
+    let name = "huobi.btc.usdt.1min";
+    assert_eq!(db.ttl(), 3);
+    assert!(db.get_table_ttl(name).unwrap().is_none());
+    assert!(matches!(db.set_table_ttl(name, 60), Err(Error::TableNotFound(_))));
+    assert!(db.get_table_id_by_name(name).unwrap().is_none());
+
+    db.open_table(name).unwrap();
+    db.set_table_ttl(name, 60).unwrap();
+    assert_eq!(db.get_table_ttl(name).unwrap(), Some(60));
+    let id = db.get_table_id_by_name(name).unwrap().unwrap();
+    let ttl = db.inner.get(build_id_to_ttl_table_inner_key(id)).unwrap();
+    assert_eq!(u8s_to_u32(&ttl.unwrap()), 60);
+
+    db.clear_table_ttl(name).unwrap();
+    assert_eq!(db.get_table_ttl(name).unwrap(), Some(3));
+    assert!(db.inner.get(build_id_to_ttl_table_inner_key(id)).unwrap().is_none());
+
+    db.set_table_ttl(name, 60).unwrap();
+    db.destroy_table(name).unwrap();
+    assert!(db.inner.get(build_id_to_ttl_table_inner_key(id)).unwrap().is_none());
+    assert!(db.ttl_registry.get_table_ttl(id).is_none());
+  }
+
+  #[test]
+  fn test_table_ttl_persisted() {
+    let path = "./data/ttl_db.test_table_ttl_persisted";
+    let name = "huobi.btc.usdt.1min";
+    {
+      let db = TtlDb::open(path, 3, &Options::new()).unwrap();
+      db.open_table(name).unwrap();
+      db.set_table_ttl(name, 60).unwrap();
+    }
+    {
+      let db = TtlDb::open(path, 5, &Options::new()).unwrap();
+      assert_eq!(db.ttl(), 5);
+      assert_eq!(db.get_table_ttl(name).unwrap(), Some(60));
+    }
+    assert!(TtlDb::destroy(path).is_ok());
+  }
+
+  #[test]
+  fn test_table_keep_latest_persisted() {
+    let path = "./data/ttl_db.test_table_keep_latest_persisted";
+    let name = "huobi.btc.usdt.1min";
+    {
+      let db = TtlDb::open(path, 3, &Options::new()).unwrap();
+      assert_eq!(db.keep_latest(), 1);
+      assert!(matches!(db.set_table_keep_latest(name, 100), Err(Error::TableNotFound(_))));
+      db.open_table(name).unwrap();
+      db.set_table_keep_latest(name, 100).unwrap();
+    }
+    {
+      let mut opts = Options::new();
+      opts.set_keep_latest(10);
+      let db = TtlDb::open(path, 3, &opts).unwrap();
+      assert_eq!(db.keep_latest(), 10);
+      assert_eq!(db.get_table_keep_latest(name).unwrap(), Some(100));
+      db.clear_table_keep_latest(name).unwrap();
+      assert_eq!(db.get_table_keep_latest(name).unwrap(), Some(10));
+    }
+    assert!(TtlDb::destroy(path).is_ok());
+  }
+
+  #[test]
+  fn test_compact_filter_with_keep_latest() {
+    use std::{thread, time};
+
+    setup_with_ttl!("ttl_db.test_compact_filter_with_keep_latest"; 1; db);
+
+    let three = db.open_table("three").unwrap();
+    let none = db.open_table("none").unwrap();
+    db.set_table_keep_latest("three", 3).unwrap();
+    db.set_table_keep_latest("none", 0).unwrap();
+    for table in [&three, &none] {
+      for key in [b"k1", b"k2", b"k3", b"k4", b"k5"] {
+        table.put(key, b"v").unwrap();
+      }
+    }
+
+    thread::sleep(time::Duration::from_secs(2));
+
+    assert!(three.get(b"k2").unwrap().is_none());
+    assert!(three.get(b"k3").unwrap().is_some());
+    assert!(none.get(b"k5").unwrap().is_none());
+
+    db.inner.compact_range(None::<&[u8]>, None::<&[u8]>);
+
+    assert!(three.get_including_expired(b"k1").unwrap().is_none());
+    assert!(three.get_including_expired(b"k2").unwrap().is_none());
+    for key in [b"k3", b"k4", b"k5"] {
+      assert!(three.get(key).unwrap().is_some());
+    }
+    assert!(none.get_including_expired(b"k5").unwrap().is_none());
+  }
+
+  #[test]
+  fn test_floor_key_index() {
+    use std::{thread, time};
+
+    use bytes::Bytes;
+
+    use crate::floor_key_index::Floor;
+
+    setup_with_ttl!("ttl_db.test_floor_key_index"; 1; db);
+
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap();
+    db.set_table_keep_latest(name, 2).unwrap();
+    let id = table.id();
+    table.put(b"k1", b"v").unwrap();
+    assert_eq!(db.ttl_registry.get_floor(id), Floor::Everything);
+    let mut wb = table.new_write_batch();
+    for key in [b"k2", b"k3", b"k4"] {
+      wb.put(key, b"v");
+    }
+    wb.write().unwrap();
+    assert_eq!(db.ttl_registry.get_floor(id), Floor::From(Bytes::from_static(b"k3")));
+    table.delete(b"k4").unwrap();
+    assert_eq!(db.ttl_registry.get_floor(id), Floor::From(Bytes::from_static(b"k2")));
+
+    thread::sleep(time::Duration::from_secs(2));
+    db.inner.compact_range(None::<&[u8]>, None::<&[u8]>);
+
+    assert!(table.get_including_expired(b"k1").unwrap().is_none());
+    assert!(table.get(b"k2").unwrap().is_some());
+    assert!(table.get(b"k3").unwrap().is_some());
+
+    db.truncate_table(name).unwrap();
+    assert_eq!(db.ttl_registry.get_floor(id), Floor::Everything);
+    db.destroy_table(name).unwrap();
+    assert_eq!(db.ttl_registry.get_floor(id), Floor::Everything);
+  }
+
+  #[test]
+  fn test_enforce_retention() {
+    use crate::retention::*;
+
+    setup_with_ttl!("ttl_db.test_enforce_retention"; 60; db);
+
+    let name = "huobi.btc.usdt.1min";
+    let mut policy = RetentionPolicy::new();
+    policy.set_max_records(2);
+    assert!(matches!(db.set_table_retention(name, policy), Err(Error::TableNotFound(_))));
+    let table = db.open_table(name).unwrap();
+    db.set_table_retention(name, policy).unwrap();
+    for key in [b"k1", b"k2", b"k3"] {
+      table.put(key, b"v").unwrap();
+    }
+
+    db.enforce_retention().unwrap();
+
+    assert!(table.get_including_expired(b"k1").unwrap().is_none());
+    assert!(table.get(b"k2").unwrap().is_some());
+    assert!(table.get(b"k3").unwrap().is_some());
+  }
+
+  #[test]
+  fn test_upgrade_value_format() {
+    let path = "./data/ttl_db.test_upgrade_value_format";
+    let names = ["huobi.btc.usdt.1min", "huobi.btc.usdt.1day"];
+    let mut opts = Options::new();
+    opts.add_table_group("cold", &Options::new());
+    {
+      let db = TtlDb::open(path, 3, &opts).unwrap();
+      let tables =
+        [db.open_table(names[0]).unwrap(), db.open_table_in_group(names[1], "cold").unwrap()];
+      let mut batch = RocksdbWriteBatch::default();
+      for table in &tables {
+        for i in 0..(VALUE_FORMAT_UPGRADE_BATCH_SIZE as u32 * 2 + 1) {
+          let legacy_value =
+            [&u32_to_u8a4((now() / 1000) as u32)[..], &u32_to_u8a4(i)[..]].concat();
+          table.cf.put_into(
+            &db.inner,
+            &mut batch,
+            build_inner_key(table.id(), u32_to_u8a4(i)),
+            legacy_value,
+          );
+        }
+        table.cf.put_into(&db.inner, &mut batch, build_inner_key(table.id(), b"short"), [0, 0]);
+      }
+      db.inner.write(batch).unwrap();
+      db.inner.delete(build_info_table_inner_key(VALUE_FORMAT_ITEM_ID)).unwrap();
+    }
+    {
+      let db = TtlDb::open(path, 3, &opts).unwrap();
+      for name in names {
+        let table = db.open_table(name).unwrap();
+        for i in 0..(VALUE_FORMAT_UPGRADE_BATCH_SIZE as u32 * 2 + 1) {
+          let value = table.get(u32_to_u8a4(i)).unwrap().unwrap();
+          assert_eq!(u8s_to_u32(&value), i);
+        }
+        let short_inner_key = build_inner_key(table.id(), b"short");
+        assert_eq!(table.cf.get(&db.inner, short_inner_key).unwrap(), Some(vec![0, 0]));
+      }
+      let value_format_item_inner_key = build_info_table_inner_key(VALUE_FORMAT_ITEM_ID);
+      assert_eq!(
+        db.inner.get(value_format_item_inner_key).unwrap(),
+        Some(vec![MILLIS_VALUE_FORMAT])
+      );
+      let progress_item_inner_key =
+        build_info_table_inner_key(VALUE_FORMAT_UPGRADE_PROGRESS_ITEM_ID);
+      assert!(db.inner.get(progress_item_inner_key).unwrap().is_none());
+    }
+    assert!(TtlDb::destroy(path).is_ok());
+  }
+
+  #[test]
+  fn test_compact_filter_with_table_ttl() {
+    use std::{thread, time};
+
+    setup_with_ttl!("ttl_db.test_compact_filter_with_table_ttl"; 100; db);
+
+    let short = db.open_table("short").unwrap();
+    db.set_table_ttl("short", 1).unwrap();
+    let long = db.open_table("long").unwrap();
+    for table in [&short, &long] {
+      table.put(b"k1", b"a").unwrap();
+      table.put(b"k2", b"b").unwrap();
+    }
+
+    thread::sleep(time::Duration::from_secs(2));
+    db.inner.compact_range(None::<&[u8]>, None::<&[u8]>);
+
+    assert!(short.get(b"k1").unwrap().is_none());
+    assert_eq!(&*short.get(b"k2").unwrap().unwrap(), b"b");
+    assert_eq!(&*long.get(b"k1").unwrap().unwrap(), b"a");
+    assert_eq!(&*long.get(b"k2").unwrap().unwrap(), b"b");
+  }
+
+  #[test]
+  fn test_key_timestamp_extractor() {
+    use crate::key_timestamp_extractor::SecsKeyTimestampExtractor;
+
+    setup_with_ttl!("ttl_db.test_key_timestamp_extractor"; 60; db);
+
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap();
+    db.set_table_key_timestamp_extractor(name, Arc::new(SecsKeyTimestampExtractor)).unwrap();
+    let secs = (now() / 1000) as u32;
+    let old_key = u32_to_u8a4(secs - 3600);
+    let new_key = u32_to_u8a4(secs);
+    table.put(old_key, b"a").unwrap();
+    table.put(new_key, b"b").unwrap();
+
+    let inner_value = db.inner.get(build_inner_key(table.id(), new_key)).unwrap().unwrap();
+    assert_eq!(inner_value, vec![KEY_TIMESTAMPED_HEADER_KIND, b'b']);
+    assert!(table.get(old_key).unwrap().is_none());
+    assert_eq!(&*table.get(new_key).unwrap().unwrap(), b"b");
+
+    db.clear_table_key_timestamp_extractor(name).unwrap();
+    assert_eq!(&*table.get(old_key).unwrap().unwrap(), b"a");
+
+    let other_name = "huobi.eth.usdt.1min";
+    assert!(matches!(
+      db.set_table_key_timestamp_extractor(other_name, Arc::new(SecsKeyTimestampExtractor)),
+      Err(Error::TableNotFound(_))
+    ));
+    let other_id = db.open_table(other_name).unwrap().id();
+    db.set_table_key_timestamp_extractor(other_name, Arc::new(SecsKeyTimestampExtractor)).unwrap();
+    db.destroy_table(other_name).unwrap();
+    assert!(db.ttl_registry.get_key_timestamp_extractor(other_id).is_none());
+
+    db.set_key_timestamp_extractor(Arc::new(SecsKeyTimestampExtractor));
+    db.inner.compact_range(None::<&[u8]>, None::<&[u8]>);
+    assert!(table.get_including_expired(old_key).unwrap().is_none());
+    assert_eq!(&*table.get(new_key).unwrap().unwrap(), b"b");
+  }
+
+  #[test]
+  fn test_key_timestamp_extractor_opts() {
+    use crate::key_timestamp_extractor::SecsKeyTimestampExtractor;
+    use crate::options::Options;
+    use crate::utils::test_utils::TestContext;
+
+    let name = "huobi.btc.usdt.1min";
+    let mut opts = Options::new();
+    opts.set_table_key_timestamp_extractor(name, Arc::new(SecsKeyTimestampExtractor));
+    let ctx =
+      TestContext::with_ttl_and_opts("ttl_db.test_key_timestamp_extractor_opts", 60, &mut opts);
+    let db = ctx.db();
+
+    let table = db.open_table(name).unwrap();
+    let other_table = db.open_table("other").unwrap();
+    let secs = (now() / 1000) as u32;
+    let old_key = u32_to_u8a4(secs - 3600);
+    table.put(old_key, b"a").unwrap();
+    other_table.put(old_key, b"a").unwrap();
+
+    assert!(table.get(old_key).unwrap().is_none());
+    assert_eq!(&*other_table.get(old_key).unwrap().unwrap(), b"a");
+  }
+
   #[test]
   fn test_compact_filter() {
     use std::{thread, time};
@@ -273,7 +874,7 @@ mod tests {
         let db = db.clone();
 
         thread::spawn(move || {
-          let table = db.create_table("test_compact_filter").unwrap();
+          let table = db.create_table("test_compact_filter", DEFAULT_TABLE_GROUP).unwrap();
 
           let _ = table.put(b"k1", b"a");
           let _ = table.put(b"k2", b"b");
@@ -289,7 +890,7 @@ mod tests {
 
           thread::sleep(time::Duration::from_secs(2));
 
-          assert_eq!(&*table.get(b"k1").unwrap().unwrap(), b"a");
+          assert_eq!(&*table.get_including_expired(b"k1").unwrap().unwrap(), b"a");
           assert_eq!(db.inner.get(&placehoder_key).unwrap(), Some(vec![0, 0]));
 
           db.inner.compact_range(None::<&[u8]>, None::<&[u8]>);
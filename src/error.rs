@@ -12,9 +12,51 @@ pub enum Error {
   #[error("Inconsistent ttl enabled: current: {current}, wanted: {wanted}")]
   InconsistentTtlEnabled { current: bool, wanted: bool },
 
+  #[error("Invalid table id: {0:?}")]
+  InvalidTableId(Vec<u8>),
+
+  #[error("Invalid table name: {0}")]
+  InvalidTableName(#[from] std::str::Utf8Error),
+
+  #[error("Corrupted value: {0:?}")]
+  CorruptedValue(Vec<u8>),
+
+  #[error("Table not found: {0}")]
+  TableNotFound(String),
+
+  #[error("Table already exists: {0}")]
+  TableAlreadyExists(String),
+
+  #[error("Table destroyed: {0}")]
+  TableDestroyed(u32),
+
+  #[error("Alias not found: {0}")]
+  AliasNotFound(String),
+
+  #[error("Table group not found: {0}")]
+  TableGroupNotFound(String),
+
+  #[error("Table group mismatch: {name}: current: {current}, wanted: {wanted}")]
+  TableGroupMismatch { name: String, current: String, wanted: String },
+
+  #[error("Invalid page token: {0:?}")]
+  InvalidPageToken(Vec<u8>),
+
+  #[error("Invalid bucket width: {0}")]
+  InvalidBucketWidth(u64),
+
+  #[error("Rollup not found: {0}")]
+  RollupNotFound(String),
+
+  #[error("Invalid rollup: {from} -> {to}")]
+  InvalidRollup { from: String, to: String },
+
   #[error(transparent)]
   ErrorPtr(#[from] Arc<Error>),
 
   #[error(transparent)]
   RocksdbError(#[from] RocksdbError),
+
+  #[error(transparent)]
+  DecodeError(#[from] prost::DecodeError),
 }
@@ -0,0 +1,321 @@
+use std::{
+  collections::BTreeSet,
+  ops::Bound,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, RwLock,
+  },
+};
+
+use ahash::AHashMap;
+use bytes::Bytes;
+use rocksdb::{WriteBatchIterator, DB as RocksdbDb};
+
+use crate::column_family::ColumnFamily;
+use crate::consts::*;
+use crate::cursor::*;
+use crate::error::Error;
+use crate::table::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// Holds the newest `keep_latest` keys of every table of a ttl db in memory, so compactions and
+/// reads can tell which keys never expire without going to the db. Puts only ever raise the
+/// smallest of them, which is called the floor key here, so they update it in place. Deletes
+/// which touch the newest keys protect the whole table until the newest keys have been read
+/// again, and bump the generation, so compactions drop the floor keys they have cached.
+pub(crate) struct FloorKeyIndex {
+  tables: RwLock<AHashMap<TableId, Arc<Mutex<NewestKeys>>>>,
+  generation: AtomicU64,
+}
+
+#[derive(Default)]
+struct NewestKeys {
+  keys: BTreeSet<Bytes>,
+  pending_deletes: u32,
+}
+
+/// The keys of a table which never expire.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Floor {
+  /// Every key, since the table does not have more than `keep_latest` keys, or its newest keys
+  /// are being deleted.
+  Everything,
+  /// The given key and the ones after it.
+  From(Bytes),
+  /// No key, since `keep_latest` is 0.
+  Nothing,
+}
+
+impl Floor {
+  #[inline]
+  pub(crate) fn protects(&self, key: &[u8]) -> bool {
+    match self {
+      Floor::Everything => true,
+      Floor::From(floor_key) => key >= &floor_key[..],
+      Floor::Nothing => false,
+    }
+  }
+}
+
+impl FloorKeyIndex {
+  #[inline]
+  pub(crate) fn new() -> Self {
+    FloorKeyIndex { tables: RwLock::new(AHashMap::new()), generation: AtomicU64::new(0) }
+  }
+
+  #[inline]
+  pub(crate) fn generation(&self) -> u64 {
+    self.generation.load(Ordering::Acquire)
+  }
+
+  #[inline]
+  pub(crate) fn get_floor(&self, table_id: TableId, keep_latest: u32) -> Floor {
+    if keep_latest == 0 {
+      return Floor::Nothing;
+    }
+    let newest_keys = match self.tables.read().unwrap().get(&table_id) {
+      Some(newest_keys) => newest_keys.clone(),
+      None => return Floor::Everything,
+    };
+    let newest_keys = newest_keys.lock().unwrap();
+    if newest_keys.pending_deletes > 0 || (newest_keys.keys.len() as u32) < keep_latest {
+      Floor::Everything
+    } else {
+      Floor::From(newest_keys.keys.first().unwrap().clone())
+    }
+  }
+
+  #[inline]
+  pub(crate) fn on_put(&self, table_id: TableId, key: &[u8], keep_latest: u32) {
+    if keep_latest == 0 {
+      return;
+    }
+    let newest_keys = self.get_or_insert(table_id);
+    let mut newest_keys = newest_keys.lock().unwrap();
+    let keys = &mut newest_keys.keys;
+    if (keys.len() as u32) < keep_latest
+      || (key > &keys.first().unwrap()[..] && !keys.contains(key))
+    {
+      keys.insert(Bytes::copy_from_slice(key));
+    }
+    while keys.len() as u32 > keep_latest {
+      keys.pop_first();
+    }
+  }
+
+  /// Applies the changes around the given write.
+  pub(crate) fn track<F, K>(
+    &self, inner_db: &Arc<RocksdbDb>, changes: FloorKeyChanges, keep_latest: K, write: F,
+  ) -> Result<(), Error>
+  where
+    F: FnOnce() -> Result<(), rocksdb::Error>,
+    K: Fn(TableId) -> u32, {
+    let deleted_from = self.begin_deletes(&changes.deletions);
+    let result = write();
+    if result.is_ok() {
+      for (table_id, key) in &changes.puts {
+        self.on_put(*table_id, key, keep_latest(*table_id));
+      }
+    }
+    for table_id in deleted_from {
+      self.end_delete(inner_db, table_id, keep_latest(table_id));
+    }
+    Ok(result?)
+  }
+
+  pub(crate) fn refresh(
+    &self, inner_db: &Arc<RocksdbDb>, table_id: TableId, keep_latest: u32,
+  ) -> Result<(), Error> {
+    let newest_keys = self.get_or_insert(table_id);
+    let mut newest_keys = newest_keys.lock().unwrap();
+    newest_keys.keys = Self::load_newest_keys(inner_db, table_id, keep_latest)?;
+    self.generation.fetch_add(1, Ordering::AcqRel);
+    Ok(())
+  }
+
+  #[inline]
+  pub(crate) fn remove(&self, table_id: TableId) {
+    self.tables.write().unwrap().remove(&table_id);
+    self.generation.fetch_add(1, Ordering::AcqRel);
+  }
+
+  fn begin_deletes(&self, deletions: &[(TableId, Bound<Bytes>, Bound<Bytes>)]) -> Vec<TableId> {
+    let mut deleted_from = Vec::new();
+    let tables = self.tables.read().unwrap();
+    for (table_id, from, to) in deletions {
+      if deleted_from.contains(table_id) {
+        continue;
+      }
+      if let Some(newest_keys) = tables.get(table_id) {
+        let mut newest_keys = newest_keys.lock().unwrap();
+        if newest_keys.is_touched_by(from, to) {
+          newest_keys.pending_deletes += 1;
+          deleted_from.push(*table_id);
+        }
+      }
+    }
+    if !deleted_from.is_empty() {
+      self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+    deleted_from
+  }
+
+  // The newest keys are read while holding the lock, so the last delete to finish sees the
+  // effects of every other one. If they cannot be read, the table stays fully protected.
+  fn end_delete(&self, inner_db: &Arc<RocksdbDb>, table_id: TableId, keep_latest: u32) {
+    let newest_keys = self.get_or_insert(table_id);
+    let mut newest_keys = newest_keys.lock().unwrap();
+    match Self::load_newest_keys(inner_db, table_id, keep_latest) {
+      Ok(keys) => {
+        newest_keys.keys = keys;
+        newest_keys.pending_deletes = newest_keys.pending_deletes.saturating_sub(1);
+      }
+      Err(err) => {
+        log::error!("Protecting every key, since it's failed to load the newest keys: table_id: {:?}, err: {:?}", table_id, err);
+      }
+    }
+  }
+
+  #[inline]
+  fn get_or_insert(&self, table_id: TableId) -> Arc<Mutex<NewestKeys>> {
+    if let Some(newest_keys) = self.tables.read().unwrap().get(&table_id) {
+      return newest_keys.clone();
+    }
+    self.tables.write().unwrap().entry(table_id).or_default().clone()
+  }
+
+  fn load_newest_keys(
+    inner_db: &Arc<RocksdbDb>, table_id: TableId, keep_latest: u32,
+  ) -> Result<BTreeSet<Bytes>, Error> {
+    let mut keys = BTreeSet::new();
+    let cf = ColumnFamily::of_table(inner_db, table_id)?;
+    let table = NormalTable::with_cf(inner_db.clone(), table_id, cf);
+    let mut cursor = table.new_cursor();
+    cursor.seek_to_last();
+    while cursor.is_valid() && (keys.len() as u32) < keep_latest {
+      keys.insert(Bytes::copy_from_slice(cursor.key().unwrap()));
+      cursor.prev();
+    }
+    cursor.status()?;
+    Ok(keys)
+  }
+}
+
+impl NewestKeys {
+  #[inline]
+  fn is_touched_by(&self, from: &Bound<Bytes>, to: &Bound<Bytes>) -> bool {
+    match self.keys.range::<Bytes, _>((from.as_ref(), Bound::Unbounded)).next() {
+      Some(key) => match to {
+        Bound::Included(to) => key <= to,
+        Bound::Excluded(to) => key < to,
+        Bound::Unbounded => true,
+      },
+      None => false,
+    }
+  }
+}
+
+/// The keys put to and deleted from userland tables by a write.
+#[derive(Default)]
+pub(crate) struct FloorKeyChanges {
+  puts: Vec<(TableId, Bytes)>,
+  deletions: Vec<(TableId, Bound<Bytes>, Bound<Bytes>)>,
+}
+
+impl FloorKeyChanges {
+  #[inline]
+  pub(crate) fn delete_key(&mut self, table_id: TableId, key: &[u8]) {
+    let key = Bytes::copy_from_slice(key);
+    self.deletions.push((table_id, Bound::Included(key.clone()), Bound::Included(key)));
+  }
+}
+
+impl WriteBatchIterator for FloorKeyChanges {
+  #[inline]
+  fn put(&mut self, inner_key: Box<[u8]>, _inner_value: Box<[u8]>) {
+    if let Some(table_id) = extract_userland_table_id(&inner_key) {
+      let key = Bytes::from(inner_key.into_vec()).slice(TABLE_ID_LEN + 1..);
+      self.puts.push((table_id, key));
+    }
+  }
+
+  #[inline]
+  fn delete(&mut self, inner_key: Box<[u8]>) {
+    if let Some(table_id) = extract_userland_table_id(&inner_key) {
+      self.delete_key(table_id, extract_key(&inner_key));
+    }
+  }
+
+  // Ranges bounded by anchors, or running into another table, cover the rest of the table.
+  #[inline]
+  fn delete_range(&mut self, begin_inner_key: Box<[u8]>, end_inner_key: Box<[u8]>) {
+    if let Some(table_id) = extract_userland_table_id(&begin_inner_key) {
+      let from = match extract_key(&begin_inner_key) {
+        [] => Bound::Unbounded,
+        key => Bound::Included(Bytes::copy_from_slice(key)),
+      };
+      let to =
+        if end_inner_key.len() > TABLE_ID_LEN + 1 && extract_table_id(&end_inner_key) == table_id {
+          Bound::Excluded(Bytes::copy_from_slice(extract_key(&end_inner_key)))
+        } else {
+          Bound::Unbounded
+        };
+      self.deletions.push((table_id, from, to));
+    }
+  }
+
+  #[inline]
+  fn merge(&mut self, _inner_key: Box<[u8]>, _inner_value: Box<[u8]>) {}
+}
+
+#[inline]
+pub(crate) fn extract_userland_table_id(inner_key: &[u8]) -> Option<TableId> {
+  if inner_key.len() < TABLE_ID_LEN + 1 {
+    return None;
+  }
+  let table_id = u8s_to_u8a4(extract_table_id(inner_key));
+  if table_id < MIN_USERLAND_TABLE_ID {
+    None
+  } else {
+    Some(table_id)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_on_put() {
+    let index = FloorKeyIndex::new();
+    let table_id = MIN_USERLAND_TABLE_ID;
+    assert_eq!(index.get_floor(table_id, 2), Floor::Everything);
+    index.on_put(table_id, b"k2", 2);
+    assert_eq!(index.get_floor(table_id, 2), Floor::Everything);
+    index.on_put(table_id, b"k3", 2);
+    assert_eq!(index.get_floor(table_id, 2), Floor::From(Bytes::from_static(b"k2")));
+    index.on_put(table_id, b"k1", 2);
+    index.on_put(table_id, b"k3", 2);
+    assert_eq!(index.get_floor(table_id, 2), Floor::From(Bytes::from_static(b"k2")));
+    index.on_put(table_id, b"k4", 2);
+    assert_eq!(index.get_floor(table_id, 2), Floor::From(Bytes::from_static(b"k3")));
+    assert_eq!(index.get_floor(table_id, 0), Floor::Nothing);
+    assert!(!Floor::From(Bytes::from_static(b"k3")).protects(b"k2"));
+    assert!(Floor::From(Bytes::from_static(b"k3")).protects(b"k3"));
+  }
+
+  #[test]
+  fn test_is_touched_by() {
+    let mut newest_keys = NewestKeys::default();
+    newest_keys.keys.insert(Bytes::from_static(b"k2"));
+    newest_keys.keys.insert(Bytes::from_static(b"k4"));
+    let key = |key: &'static [u8]| Bytes::from_static(key);
+    assert!(newest_keys.is_touched_by(&Bound::Included(key(b"k2")), &Bound::Included(key(b"k2"))));
+    assert!(!newest_keys.is_touched_by(&Bound::Included(key(b"k3")), &Bound::Included(key(b"k3"))));
+    assert!(!newest_keys.is_touched_by(&Bound::Included(key(b"k1")), &Bound::Excluded(key(b"k2"))));
+    assert!(newest_keys.is_touched_by(&Bound::Included(key(b"k3")), &Bound::Unbounded));
+    assert!(newest_keys.is_touched_by(&Bound::Unbounded, &Bound::Excluded(key(b"k3"))));
+    assert!(!newest_keys.is_touched_by(&Bound::Included(key(b"k5")), &Bound::Unbounded));
+  }
+}
@@ -0,0 +1,55 @@
+use crate::utils::*;
+
+/// Derives the timestamp of a record, in milliseconds, from its key. Returns `None` for keys which
+/// do not carry one.
+pub trait KeyTimestampExtractor: Send + Sync {
+  fn extract(&self, key: &[u8]) -> Option<u64>;
+}
+
+impl<F: Fn(&[u8]) -> Option<u64> + Send + Sync> KeyTimestampExtractor for F {
+  #[inline]
+  fn extract(&self, key: &[u8]) -> Option<u64> {
+    self(key)
+  }
+}
+
+/// Keys starting with a big endian `u32` in seconds.
+pub struct SecsKeyTimestampExtractor;
+
+impl KeyTimestampExtractor for SecsKeyTimestampExtractor {
+  #[inline]
+  fn extract(&self, key: &[u8]) -> Option<u64> {
+    if key.len() < 4 {
+      return None;
+    }
+    Some(u8s_to_u32(key) as u64 * 1000)
+  }
+}
+
+/// Keys starting with a big endian `u64` in milliseconds.
+pub struct MillisKeyTimestampExtractor;
+
+impl KeyTimestampExtractor for MillisKeyTimestampExtractor {
+  #[inline]
+  fn extract(&self, key: &[u8]) -> Option<u64> {
+    if key.len() < 8 {
+      return None;
+    }
+    Some(u8s_to_u64(key))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_extract() {
+    assert_eq!(SecsKeyTimestampExtractor.extract(&[0, 0, 1, 0, 9]), Some(256000));
+    assert_eq!(SecsKeyTimestampExtractor.extract(&[0, 1]), None);
+    assert_eq!(MillisKeyTimestampExtractor.extract(&[0, 0, 0, 0, 0, 0, 1, 0]), Some(256));
+    assert_eq!(MillisKeyTimestampExtractor.extract(&[0, 0, 1, 0]), None);
+    let extractor = |key: &[u8]| Some(key.len() as u64);
+    assert_eq!(extractor.extract(b"k1"), Some(2));
+  }
+}
@@ -1,11 +1,18 @@
 pub mod coder;
+pub(crate) mod column_family;
 pub(crate) mod compact_filter;
 pub mod consts;
+pub mod convert;
 pub mod cursor;
 pub mod db;
 pub mod error;
+pub(crate) mod floor_key_index;
+pub mod key_timestamp_extractor;
 pub mod options;
+pub mod retention;
+pub mod rollup;
 pub mod table;
+pub(crate) mod ttl_registry;
 pub mod types;
 pub mod utils;
 pub mod write_batch;
@@ -16,7 +23,10 @@ pub mod prelude {
   pub use crate::cursor::*;
   pub use crate::db::*;
   pub use crate::error::*;
+  pub use crate::key_timestamp_extractor::*;
   pub use crate::options::*;
+  pub use crate::retention::*;
+  pub use crate::rollup::*;
   pub use crate::table::*;
   pub use crate::types::*;
   pub use crate::write_batch::*;
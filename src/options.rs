@@ -1,20 +1,36 @@
+use std::sync::Arc;
+
 use rocksdb::{
-  compaction_filter_factory::CompactionFilterFactory, DBCompactionStyle, Options as RocksdbOptions,
-  SliceTransform,
+  compaction_filter_factory::CompactionFilterFactory, ColumnFamilyDescriptor,
+  Options as RocksdbOptions, SliceTransform, DEFAULT_COLUMN_FAMILY_NAME,
 };
+pub use rocksdb::{DBCompactionStyle, DBCompressionType};
 
+use crate::column_family::*;
 use crate::consts::*;
+use crate::key_timestamp_extractor::KeyTimestampExtractor;
 
 #[derive(Clone)]
 pub struct Options {
   pub(crate) inner: RocksdbOptions,
   pub(crate) cache_capacity: usize,
+  pub(crate) keep_latest: u32,
+  pub(crate) table_groups: Vec<(String, RocksdbOptions)>,
+  pub(crate) key_timestamp_extractor: Option<Arc<dyn KeyTimestampExtractor>>,
+  pub(crate) table_key_timestamp_extractors: Vec<(String, Arc<dyn KeyTimestampExtractor>)>,
 }
 
 impl Options {
   #[inline]
   pub fn new() -> Self {
-    Options { inner: Self::build_default_rocksdb_opts(), cache_capacity: 10240 }
+    Options {
+      inner: Self::build_default_rocksdb_opts(),
+      cache_capacity: 10240,
+      keep_latest: DEFAULT_KEEP_LATEST,
+      table_groups: Vec::new(),
+      key_timestamp_extractor: None,
+      table_key_timestamp_extractors: Vec::new(),
+    }
   }
 
   #[inline]
@@ -57,6 +73,21 @@ impl Options {
     self.inner.set_target_file_size_multiplier(size);
   }
 
+  #[inline]
+  pub fn set_compression_type(&mut self, compression_type: DBCompressionType) {
+    self.inner.set_compression_type(compression_type);
+  }
+
+  #[inline]
+  pub fn set_compaction_style(&mut self, style: DBCompactionStyle) {
+    self.inner.set_compaction_style(style);
+  }
+
+  #[inline]
+  pub fn set_disable_auto_compactions(&mut self, disable: bool) {
+    self.inner.set_disable_auto_compactions(disable);
+  }
+
   #[inline]
   pub fn set_level_zero_file_num_compaction_trigger(&mut self, num: i32) {
     self.inner.set_level_zero_file_num_compaction_trigger(num);
@@ -87,16 +118,89 @@ impl Options {
     self.cache_capacity = num;
   }
 
+  /// Only used by ttl dbs: the number of newest records per table which never expire.
+  #[inline]
+  pub fn set_keep_latest(&mut self, num: u32) {
+    self.keep_latest = num;
+  }
+
+  /// Only used by ttl dbs: derives the timestamps of the values put without one from their keys,
+  /// in every table without an extractor of its own. Extractors are not persisted, so a db whose
+  /// values were put with one must always be opened with it: it is registered before any
+  /// compaction may expire a value, whereas the values of a db opened without it never expire.
+  #[inline]
+  pub fn set_key_timestamp_extractor(&mut self, extractor: Arc<dyn KeyTimestampExtractor>) {
+    self.key_timestamp_extractor = Some(extractor);
+  }
+
+  /// Same as `set_key_timestamp_extractor`, but for the given table only, which is created when
+  /// the db is opened if needed.
   #[inline]
-  pub(crate) fn set_compaction_filter_factory(
-    &mut self, factory: impl CompactionFilterFactory + 'static,
+  pub fn set_table_key_timestamp_extractor(
+    &mut self, name: &str, extractor: Arc<dyn KeyTimestampExtractor>,
   ) {
-    self.inner.set_compaction_filter_factory(factory)
+    self.table_key_timestamp_extractors.retain(|(table, _)| table != name);
+    self.table_key_timestamp_extractors.push((name.to_string(), extractor));
+  }
+
+  /// Adds a group of tables whose data lives in a column family of its own, configured by the
+  /// given options, e.g. to compress cold tables harder than hot ones. Only the per column family
+  /// settings of those options apply, i.e. the compression, write buffers and compaction. The
+  /// default group is configured by these options, so adding it changes nothing. A db holding
+  /// a group must be opened with it.
+  #[inline]
+  pub fn add_table_group(&mut self, group: &str, opts: &Options) {
+    if group != DEFAULT_TABLE_GROUP {
+      self.table_groups.retain(|(name, _)| name != group);
+      self.table_groups.push((group.to_string(), opts.inner.clone()));
+    }
+  }
+
+  // The default group comes first, as it holds the catalog, then the others in name order, so an
+  // interrupted rewrite of every group visits them in the same order when resumed.
+  #[inline]
+  pub(crate) fn list_table_groups(&self) -> Vec<&str> {
+    let mut groups: Vec<&str> = self.table_groups.iter().map(|(group, _)| group.as_str()).collect();
+    groups.sort_unstable();
+    groups.insert(0, DEFAULT_TABLE_GROUP);
+    groups
+  }
+
+  // Each column family gets a factory of its own.
+  #[inline]
+  pub(crate) fn set_compaction_filter_factory<F: CompactionFilterFactory + 'static>(
+    &mut self, build_factory: impl Fn() -> F,
+  ) {
+    self.inner.set_compaction_filter_factory(build_factory());
+    for (_, opts) in &mut self.table_groups {
+      opts.set_compaction_filter_factory(build_factory());
+    }
+  }
+
+  #[inline]
+  pub(crate) fn build_column_family_descriptors(&self) -> Vec<ColumnFamilyDescriptor> {
+    let mut descriptors =
+      vec![ColumnFamilyDescriptor::new(DEFAULT_COLUMN_FAMILY_NAME, self.inner.clone())];
+    for (group, opts) in &self.table_groups {
+      descriptors.push(ColumnFamilyDescriptor::new(group, opts.clone()));
+    }
+    descriptors
+  }
+
+  // Batches need no routing unless there are groups besides the default one.
+  #[inline]
+  pub(crate) fn routing(&self) -> Routing {
+    if self.table_groups.is_empty() {
+      Routing::To(ColumnFamily::default())
+    } else {
+      Routing::ByTable
+    }
   }
 
   fn build_default_rocksdb_opts() -> RocksdbOptions {
     let mut opts = RocksdbOptions::default();
     opts.create_if_missing(true);
+    opts.create_missing_column_families(true);
     opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(TABLE_ID_LEN));
     opts.set_max_open_files(-1);
     opts.set_use_fsync(false);
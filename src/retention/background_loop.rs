@@ -0,0 +1,89 @@
+use std::{
+  sync::{
+    mpsc::{self, RecvTimeoutError, Sender},
+    Arc, Condvar, Mutex,
+  },
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+
+/// Calls a function every interval, in a thread of its own, until dropped, keeping what the
+/// latest call returned. Dropping it waits for the call in flight, if any.
+pub(crate) struct BackgroundLoop<T> {
+  stop_sender: Option<Sender<()>>,
+  handle: Option<JoinHandle<()>>,
+  // Signalled after each call.
+  state: Arc<(Mutex<LoopState<T>>, Condvar)>,
+}
+
+struct LoopState<T> {
+  runs: usize,
+  last: Option<T>,
+}
+
+impl<T: Send + 'static> BackgroundLoop<T> {
+  pub(crate) fn spawn<F: FnMut() -> T + Send + 'static>(interval: Duration, mut run: F) -> Self {
+    let (stop_sender, stop_receiver) = mpsc::channel::<()>();
+    let state = Arc::new((Mutex::new(LoopState { runs: 0, last: None }), Condvar::new()));
+    let handle = {
+      let state = state.clone();
+      thread::spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(interval) {
+          let last = run();
+          let (loop_state, signal) = &*state;
+          let mut loop_state = loop_state.lock().unwrap();
+          loop_state.runs += 1;
+          loop_state.last = Some(last);
+          signal.notify_all();
+        }
+      })
+    };
+    BackgroundLoop { stop_sender: Some(stop_sender), handle: Some(handle), state }
+  }
+
+  #[inline]
+  pub(crate) fn runs(&self) -> usize {
+    self.state.0.lock().unwrap().runs
+  }
+
+  #[inline]
+  pub(crate) fn last(&self) -> Option<T>
+  where T: Clone {
+    self.state.0.lock().unwrap().last.clone()
+  }
+
+  /// Blocks until the function has been called the given number of times.
+  #[cfg(test)]
+  pub(crate) fn wait_for_runs(&self, runs: usize) {
+    let (loop_state, signal) = &*self.state;
+    let _loop_state =
+      signal.wait_while(loop_state.lock().unwrap(), |loop_state| loop_state.runs < runs).unwrap();
+  }
+}
+
+impl<T> Drop for BackgroundLoop<T> {
+  fn drop(&mut self) {
+    drop(self.stop_sender.take());
+    if let Some(handle) = self.handle.take() {
+      let _ = handle.join();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_spawn() {
+    let mut count = 0;
+    let background_loop = BackgroundLoop::spawn(Duration::from_millis(1), move || {
+      count += 1;
+      count
+    });
+    background_loop.wait_for_runs(2);
+    assert!(background_loop.runs() >= 2);
+    assert!(background_loop.last().unwrap() >= 2);
+    drop(background_loop);
+  }
+}
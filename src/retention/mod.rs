@@ -0,0 +1,12 @@
+pub(crate) mod background_loop;
+pub mod retention_counts;
+pub mod retention_policy;
+pub mod retention_rule;
+pub mod retention_scheduler;
+pub mod retention_worker;
+
+pub use retention_counts::*;
+pub use retention_policy::*;
+pub use retention_rule::*;
+pub use retention_scheduler::*;
+pub use retention_worker::*;
@@ -0,0 +1,45 @@
+use std::sync::Mutex;
+
+use ahash::AHashMap;
+use bytes::Bytes;
+
+use crate::types::*;
+
+/// How many retention passes reuse the counts of a table before it is counted from scratch.
+const MAX_REUSES: u32 = 16;
+
+/// The size and the number of records of the tables as the last retention pass left them, so the
+/// next pass only walks the records put since and the ones it deletes. Counts are dropped once the
+/// oldest or the newest key counted is gone. Records deleted or overwritten in between go
+/// unnoticed, which is why the counts are only reused `MAX_REUSES` times.
+#[derive(Default)]
+pub struct RetentionCounts {
+  tables: Mutex<AHashMap<TableId, TableCounts>>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TableCounts {
+  pub(crate) oldest_key: Bytes,
+  pub(crate) newest_key: Bytes,
+  pub(crate) bytes: u64,
+  pub(crate) records: u64,
+  pub(crate) reuses: u32,
+}
+
+impl RetentionCounts {
+  /// Hands out the counts of the table, unless they have been reused too often.
+  #[inline]
+  pub(crate) fn take(&self, id: TableId) -> Option<TableCounts> {
+    self.tables.lock().unwrap().remove(&id).filter(|counts| counts.reuses < MAX_REUSES)
+  }
+
+  #[inline]
+  pub(crate) fn insert(&self, id: TableId, counts: TableCounts) {
+    self.tables.lock().unwrap().insert(id, counts);
+  }
+
+  #[inline]
+  pub(crate) fn remove(&self, id: TableId) {
+    self.tables.lock().unwrap().remove(&id);
+  }
+}
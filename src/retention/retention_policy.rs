@@ -0,0 +1,83 @@
+use crate::error::Error;
+use crate::utils::*;
+
+/// Caps on the size and the number of records of a table. Once a table exceeds any of them, its
+/// oldest records, in key order, are deleted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+  pub(crate) max_bytes: Option<u64>,
+  pub(crate) max_records: Option<u64>,
+}
+
+impl RetentionPolicy {
+  #[inline]
+  pub fn new() -> Self {
+    RetentionPolicy::default()
+  }
+
+  /// Keys and values are counted as they are stored. The newest record is kept even if it alone
+  /// exceeds the cap.
+  #[inline]
+  pub fn set_max_bytes(&mut self, bytes: u64) {
+    self.max_bytes = Some(bytes);
+  }
+
+  /// The newest record is kept even if the cap is 0.
+  #[inline]
+  pub fn set_max_records(&mut self, num: u64) {
+    self.max_records = Some(num);
+  }
+
+  #[inline(always)]
+  pub fn max_bytes(&self) -> Option<u64> {
+    self.max_bytes
+  }
+
+  #[inline(always)]
+  pub fn max_records(&self) -> Option<u64> {
+    self.max_records
+  }
+
+  #[inline]
+  pub(crate) fn is_exceeded(&self, bytes: u64, records: u64) -> bool {
+    self.max_bytes.is_some_and(|max_bytes| bytes > max_bytes)
+      || self.max_records.is_some_and(|max_records| records > max_records)
+  }
+
+  // Missing caps are stored as u64::MAX.
+  #[inline]
+  pub(crate) fn encode(&self) -> Vec<u8> {
+    [
+      u64_to_u8a8(self.max_bytes.unwrap_or(u64::MAX)),
+      u64_to_u8a8(self.max_records.unwrap_or(u64::MAX)),
+    ]
+    .concat()
+  }
+
+  // Returns `Error::CorruptedValue` if the buffer is too short to hold both caps.
+  #[inline]
+  pub(crate) fn decode(buf: &[u8]) -> Result<Self, Error> {
+    let buf = buf.get(..16).ok_or_else(|| Error::CorruptedValue(buf.to_vec()))?;
+    let decode_cap = |buf: &[u8]| Some(u8s_to_u64(buf)).filter(|cap| *cap != u64::MAX);
+    Ok(RetentionPolicy { max_bytes: decode_cap(&buf[..8]), max_records: decode_cap(&buf[8..]) })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_encode() {
+    let mut policy = RetentionPolicy::new();
+    assert_eq!(RetentionPolicy::decode(&policy.encode()).unwrap(), policy);
+    policy.set_max_records(10);
+    assert_eq!(RetentionPolicy::decode(&policy.encode()).unwrap(), policy);
+    assert!(!policy.is_exceeded(u64::MAX, 10));
+    assert!(policy.is_exceeded(0, 11));
+    policy.set_max_bytes(1024);
+    assert_eq!(RetentionPolicy::decode(&policy.encode()).unwrap(), policy);
+    assert!(policy.is_exceeded(1025, 1));
+    assert!(matches!(RetentionPolicy::decode(&[0; 15]), Err(Error::CorruptedValue(_))));
+  }
+}
@@ -0,0 +1,27 @@
+use crate::utils::*;
+
+/// Deletes the keys older than a cut-off key from every table whose name matches the pattern, in
+/// which `*` stands for any run of characters and `?` for any single one. The cut-off key is
+/// computed from the current time, in milliseconds, on every run.
+pub struct RetentionRule {
+  pub(crate) pattern: String,
+  pub(crate) cut_off: Box<dyn Fn(u64) -> Vec<u8> + Send + Sync>,
+}
+
+impl RetentionRule {
+  #[inline]
+  pub fn new<F, K>(pattern: &str, cut_off: F) -> Self
+  where
+    F: Fn(u64) -> K + Send + Sync + 'static,
+    K: AsRef<[u8]>, {
+    RetentionRule {
+      pattern: pattern.to_string(),
+      cut_off: Box::new(move |now| cut_off(now).as_ref().to_vec()),
+    }
+  }
+
+  #[inline]
+  pub(crate) fn matches(&self, name: &str) -> bool {
+    matches_glob(&self.pattern, name)
+  }
+}
@@ -0,0 +1,126 @@
+use std::{
+  sync::Arc,
+  time::{Duration, Instant},
+};
+
+use rocksdb::DB as RocksdbDb;
+
+use super::background_loop::BackgroundLoop;
+use super::retention_rule::RetentionRule;
+use crate::column_family::ColumnFamily;
+use crate::consts::*;
+use crate::cursor::*;
+use crate::error::Error;
+use crate::rollup::RollupRegistry;
+use crate::table::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// What a run of the retention scheduler did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RetentionStats {
+  /// When the run started, in milliseconds.
+  pub started_at: u64,
+  pub elapsed: Duration,
+  pub tables_matched: usize,
+  /// The tables which had keys older than their cut-off key.
+  pub tables_trimmed: usize,
+  pub errors: usize,
+}
+
+/// Applies retention rules to the tables of a db every interval, in a thread of its own, until
+/// dropped.
+pub(crate) struct RetentionScheduler {
+  pub(crate) background_loop: BackgroundLoop<RetentionStats>,
+}
+
+impl RetentionScheduler {
+  pub(crate) fn spawn(
+    inner_db: Arc<RocksdbDb>, rollups: Arc<RollupRegistry>, rules: Vec<RetentionRule>,
+    interval: Duration,
+  ) -> Self {
+    let background_loop = BackgroundLoop::spawn(interval, move || {
+      let stats = Self::run(&inner_db, &rollups, &rules);
+      log::info!("Applied retention rules: stats: {:?}", stats);
+      stats
+    });
+    RetentionScheduler { background_loop }
+  }
+
+  #[inline]
+  pub(crate) fn last_stats(&self) -> Option<RetentionStats> {
+    self.background_loop.last()
+  }
+
+  pub(crate) fn run(
+    inner_db: &Arc<RocksdbDb>, rollups: &Arc<RollupRegistry>, rules: &[RetentionRule],
+  ) -> RetentionStats {
+    let started = Instant::now();
+    let mut stats = RetentionStats { started_at: now(), ..Default::default() };
+    let tables = match Self::get_tables(inner_db) {
+      Ok(tables) => tables,
+      Err(err) => {
+        log::error!("Failed to list tables for retention: err: {:?}", err);
+        stats.errors += 1;
+        Vec::new()
+      }
+    };
+    for (id, name) in tables {
+      // The first matching rule wins.
+      if let Some(rule) = rules.iter().find(|rule| rule.matches(&name)) {
+        stats.tables_matched += 1;
+        match Self::apply(inner_db, rollups, id, &(rule.cut_off)(stats.started_at)) {
+          Ok(true) => stats.tables_trimmed += 1,
+          Ok(false) => (),
+          Err(err) => {
+            log::error!("Failed to apply retention rule: name: {:?}, err: {:?}", name, err);
+            stats.errors += 1;
+          }
+        }
+      }
+    }
+    stats.elapsed = started.elapsed();
+    stats
+  }
+
+  // Deletes the keys below the cut-off key, updating the rollups derived from the table, and
+  // compacts their range, unless there are none.
+  fn apply(
+    inner_db: &Arc<RocksdbDb>, rollups: &Arc<RollupRegistry>, id: TableId, cut_off_key: &[u8],
+  ) -> Result<bool, Error> {
+    let cf = ColumnFamily::of_table(inner_db, id)?;
+    let table =
+      NormalTable::with_liveness(inner_db.clone(), id, cf, Arc::default(), rollups.clone());
+    {
+      let mut cursor = table.new_cursor();
+      cursor.seek_to_first();
+      cursor.status()?;
+      if !matches!(cursor.key(), Some(key) if key < cut_off_key) {
+        return Ok(false);
+      }
+    }
+    table.delete_range(b"", cut_off_key)?;
+    table.cf.compact_range(
+      inner_db,
+      build_inner_key(table.id, b""),
+      build_inner_key(table.id, cut_off_key),
+    );
+    Ok(true)
+  }
+
+  fn get_tables(inner_db: &Arc<RocksdbDb>) -> Result<Vec<(TableId, String)>, Error> {
+    let mut tables = Vec::new();
+    let id_to_name_table = NormalTable::new(inner_db.clone(), ID_TO_NAME_TABLE_ID);
+    let mut cursor = id_to_name_table.new_cursor();
+    cursor.seek_to_first();
+    while cursor.is_valid() {
+      tables.push((
+        u8s_to_u8a4(cursor.key().unwrap()),
+        String::from_utf8_lossy(cursor.value().unwrap()).into_owned(),
+      ));
+      cursor.next();
+    }
+    cursor.status()?;
+    Ok(tables)
+  }
+}
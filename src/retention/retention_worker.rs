@@ -0,0 +1,57 @@
+use std::{sync::Arc, time::Duration};
+
+use super::background_loop::BackgroundLoop;
+use crate::db::*;
+
+/// Calls `enforce_retention` on the db every interval, in a thread of its own, until dropped.
+pub struct RetentionWorker {
+  background_loop: BackgroundLoop<()>,
+}
+
+impl RetentionWorker {
+  pub fn spawn<D: Db + Send + Sync + 'static>(db: Arc<D>, interval: Duration) -> Self {
+    let background_loop = BackgroundLoop::spawn(interval, move || {
+      if let Err(err) = db.enforce_retention() {
+        log::error!("Failed to enforce retention policies: err: {:?}", err);
+      }
+    });
+    RetentionWorker { background_loop }
+  }
+
+  /// Returns the number of times `enforce_retention` was called so far.
+  #[inline]
+  pub fn runs(&self) -> usize {
+    self.background_loop.runs()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::retention::*;
+  use crate::setup;
+  use crate::table::*;
+
+  #[test]
+  fn test_spawn() {
+    setup!("retention_worker.test_spawn"; db);
+
+    let name = "huobi.btc.usdt.1min";
+    let mut policy = RetentionPolicy::new();
+    policy.set_max_records(2);
+    let table = db.open_table(name).unwrap();
+    db.set_table_retention(name, policy).unwrap();
+    for key in [b"k1", b"k2", b"k3"] {
+      table.put(key, b"v").unwrap();
+    }
+
+    let worker = RetentionWorker::spawn(Arc::clone(&db), Duration::from_millis(10));
+    worker.background_loop.wait_for_runs(1);
+    assert!(worker.runs() >= 1);
+    drop(worker);
+
+    assert!(table.get(b"k1").unwrap().is_none());
+    assert!(table.get(b"k2").unwrap().is_some());
+    assert!(table.get(b"k3").unwrap().is_some());
+  }
+}
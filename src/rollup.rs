@@ -0,0 +1,496 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex, RwLock};
+
+use ahash::AHashMap;
+use bytes::Bytes;
+use rocksdb::{WriteBatch as RocksdbWriteBatch, WriteBatchIterator, DB as RocksdbDb};
+
+use crate::column_family::{ColumnFamily, Routing};
+use crate::consts::*;
+use crate::error::Error;
+use crate::floor_key_index::extract_userland_table_id;
+use crate::table::*;
+use crate::ttl_registry::TtlRegistry;
+use crate::types::*;
+use crate::utils::*;
+
+/// Maps the keys of the source table of a rollup to the buckets of its target table.
+pub trait KeyBucketing: Send + Sync {
+  /// Returns the first key of the bucket holding the key, which is also the key of the bucket in
+  /// the target table, and the first key of the next bucket, if any. Returns `None` for keys
+  /// which belong to no bucket.
+  fn bucket(&self, key: &[u8]) -> Option<(Bytes, Option<Bytes>)>;
+}
+
+impl<F: Fn(&[u8]) -> Option<(Bytes, Option<Bytes>)> + Send + Sync> KeyBucketing for F {
+  #[inline]
+  fn bucket(&self, key: &[u8]) -> Option<(Bytes, Option<Bytes>)> {
+    self(key)
+  }
+}
+
+/// Keys starting with a big endian `u32` in seconds, in buckets of the given number of seconds.
+pub struct SecsKeyBucketing(pub u32);
+
+impl SecsKeyBucketing {
+  /// Buckets as wide as the interval the table name ends with, e.g. `huobi.btc.usdt.5min`.
+  #[inline]
+  pub fn from_table_name(name: &str) -> Option<Self> {
+    let secs = parse_interval_secs(name)?;
+    u32::try_from(secs).ok().map(SecsKeyBucketing)
+  }
+}
+
+impl KeyBucketing for SecsKeyBucketing {
+  #[inline]
+  fn bucket(&self, key: &[u8]) -> Option<(Bytes, Option<Bytes>)> {
+    if key.len() < 4 || self.0 == 0 {
+      return None;
+    }
+    let start = u8s_to_u32(key) / self.0 * self.0;
+    let end = start.checked_add(self.0).map(|end| Bytes::copy_from_slice(&u32_to_u8a4(end)));
+    Some((Bytes::copy_from_slice(&u32_to_u8a4(start)), end))
+  }
+}
+
+/// Keys starting with a big endian `u64` in milliseconds, in buckets of the given number of
+/// milliseconds.
+pub struct MillisKeyBucketing(pub u64);
+
+impl MillisKeyBucketing {
+  /// Buckets as wide as the interval the table name ends with, e.g. `huobi.btc.usdt.5min`.
+  #[inline]
+  pub fn from_table_name(name: &str) -> Option<Self> {
+    let secs = parse_interval_secs(name)?;
+    secs.checked_mul(1000).map(MillisKeyBucketing)
+  }
+}
+
+impl KeyBucketing for MillisKeyBucketing {
+  #[inline]
+  fn bucket(&self, key: &[u8]) -> Option<(Bytes, Option<Bytes>)> {
+    if key.len() < 8 || self.0 == 0 {
+      return None;
+    }
+    let start = u8s_to_u64(key) / self.0 * self.0;
+    let end = start.checked_add(self.0).map(|end| Bytes::copy_from_slice(&u64_to_u8a8(end)));
+    Some((Bytes::copy_from_slice(&u64_to_u8a8(start)), end))
+  }
+}
+
+/// Returns the interval, in seconds, the table name ends with, e.g. 300 for `huobi.btc.usdt.5min`.
+/// The units are `s`, `sec`, `m`, `min`, `h`, `hour`, `d`, `day`, `w` and `week`, the long ones
+/// optionally in plural.
+pub fn parse_interval_secs(name: &str) -> Option<u64> {
+  let interval = name.rsplit('.').next()?;
+  let digits = interval.bytes().take_while(u8::is_ascii_digit).count();
+  let count: u64 = interval[..digits].parse().ok()?;
+  let unit = match &interval[digits..] {
+    "s" | "sec" | "secs" => 1,
+    "m" | "min" | "mins" => 60,
+    "h" | "hour" | "hours" => 3600,
+    "d" | "day" | "days" => 86400,
+    "w" | "week" | "weeks" => 604800,
+    _ => return None,
+  };
+  count.checked_mul(unit)
+}
+
+/// Merges the entries of a bucket of the source table of a rollup, in key order, into the value
+/// of the bucket in its target table. Buckets left with no entry are deleted instead.
+pub trait RollupMerge: Send + Sync {
+  fn merge(&self, bucket_key: &[u8], entries: &[(Bytes, Bytes)]) -> Vec<u8>;
+}
+
+impl<F: Fn(&[u8], &[(Bytes, Bytes)]) -> Vec<u8> + Send + Sync> RollupMerge for F {
+  #[inline]
+  fn merge(&self, bucket_key: &[u8], entries: &[(Bytes, Bytes)]) -> Vec<u8> {
+    self(bucket_key, entries)
+  }
+}
+
+pub(crate) struct Rollup {
+  pub(crate) source_id: TableId,
+  pub(crate) source_cf: ColumnFamily,
+  pub(crate) target_id: TableId,
+  pub(crate) target_cf: ColumnFamily,
+  pub(crate) bucketing: Arc<dyn KeyBucketing>,
+  pub(crate) merge: Arc<dyn RollupMerge>,
+}
+
+impl Rollup {
+  // Collects the buckets the op may change, i.e. for a range, every bucket of the target in it.
+  fn touch(
+    &self, inner_db: &Arc<RocksdbDb>, op: &Op, buckets: &mut BTreeSet<Bytes>,
+  ) -> Result<(), Error> {
+    match op {
+      Op::Put(_, key, _) | Op::Delete(_, key) => {
+        if let Some((start, _)) = self.bucketing.bucket(key) {
+          buckets.insert(start);
+        }
+      }
+      Op::DeleteRange(_, from, to) => {
+        let from = match from {
+          Bound::Included(key) => match self.bucketing.bucket(key) {
+            Some((start, _)) => Bound::Included(start),
+            None => Bound::Included(key.clone()),
+          },
+          _ => Bound::Unbounded,
+        };
+        let target = NormalTable::with_cf(inner_db.clone(), self.target_id, self.target_cf.clone());
+        for entry in target.range((from, to.clone())) {
+          buckets.insert(entry?.0);
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// The rollups of a db, by their source tables. Registrations last as long as the db is open.
+#[derive(Default)]
+pub struct RollupRegistry {
+  ttl_registry: Option<Arc<TtlRegistry>>,
+  rollups: RwLock<AHashMap<TableId, Vec<Arc<Rollup>>>>,
+  // Serializes the writes which update rollups, from reading the buckets to writing them.
+  write_lock: Mutex<()>,
+}
+
+impl RollupRegistry {
+  #[inline]
+  pub(crate) fn new(ttl_registry: Option<Arc<TtlRegistry>>) -> Self {
+    RollupRegistry { ttl_registry, ..Default::default() }
+  }
+
+  // Returns false if the target is the source, is derived already from another table, or derives
+  // the source, directly or not. Registering the same pair again replaces the rollup.
+  pub(crate) fn register(&self, rollup: Rollup) -> bool {
+    let _guard = self.write_lock.lock().unwrap();
+    let mut rollups = self.rollups.write().unwrap();
+    if rollup.source_id == rollup.target_id {
+      return false;
+    }
+    let derived_from = Self::find_by_target(&rollups, rollup.target_id).map(|r| r.source_id);
+    if derived_from.is_some_and(|source_id| source_id != rollup.source_id) {
+      return false;
+    }
+    let mut pending = vec![rollup.target_id];
+    while let Some(id) = pending.pop() {
+      if id == rollup.source_id {
+        return false;
+      }
+      pending.extend(rollups.get(&id).into_iter().flatten().map(|r| r.target_id));
+    }
+    let by_source = rollups.entry(rollup.source_id).or_default();
+    by_source.retain(|r| r.target_id != rollup.target_id);
+    by_source.push(Arc::new(rollup));
+    true
+  }
+
+  pub(crate) fn unregister(&self, target_id: TableId) -> bool {
+    let _guard = self.write_lock.lock().unwrap();
+    let mut rollups = self.rollups.write().unwrap();
+    let mut found = false;
+    rollups.retain(|_, by_source| {
+      let len = by_source.len();
+      by_source.retain(|r| r.target_id != target_id);
+      found |= by_source.len() != len;
+      !by_source.is_empty()
+    });
+    found
+  }
+
+  // Waits for the writes in flight, so none of them updates a bucket of the table afterwards.
+  // Returns whether the table was derived from another one.
+  pub(crate) fn remove_table(&self, id: TableId) -> bool {
+    let _guard = self.write_lock.lock().unwrap();
+    let mut rollups = self.rollups.write().unwrap();
+    rollups.remove(&id);
+    let mut derived = false;
+    rollups.retain(|_, by_source| {
+      let len = by_source.len();
+      by_source.retain(|r| r.target_id != id);
+      derived |= by_source.len() != len;
+      !by_source.is_empty()
+    });
+    derived
+  }
+
+  #[inline]
+  pub(crate) fn get_by_target(&self, target_id: TableId) -> Option<Arc<Rollup>> {
+    Self::find_by_target(&self.rollups.read().unwrap(), target_id).cloned()
+  }
+
+  #[inline]
+  pub(crate) fn is_source(&self, id: TableId) -> bool {
+    self.rollups.read().unwrap().contains_key(&id)
+  }
+
+  /// Adds to the batch the buckets its ops change, then writes it with the given routing. Buckets
+  /// derived from those are updated too, and so on. Once buckets are added, the batch is routed
+  /// by table instead, as they may belong to other groups.
+  pub(crate) fn write<F>(
+    &self, inner_db: &Arc<RocksdbDb>, mut batch: RocksdbWriteBatch, routing: &Routing, write: F,
+  ) -> Result<(), Error>
+  where F: FnOnce(RocksdbWriteBatch, &Routing) -> Result<(), Error> {
+    if self.rollups.read().unwrap().is_empty() {
+      return write(batch, routing);
+    }
+    let mut collector = OpCollector::default();
+    batch.iterate(&mut collector);
+    if !collector.ops.iter().any(|op| self.is_source(op.table_id())) {
+      return write(batch, routing);
+    }
+    let _guard = self.write_lock.lock().unwrap();
+    let rollups = self.rollups.read().unwrap().clone();
+    let mut overlay = Overlay::default();
+    let mut ops = collector.ops;
+    while !ops.is_empty() {
+      let mut touched = BTreeMap::new();
+      for op in &ops {
+        overlay.apply(op);
+        for rollup in rollups.get(&op.table_id()).into_iter().flatten() {
+          let (_, buckets) =
+            touched.entry(rollup.target_id).or_insert_with(|| (rollup.clone(), BTreeSet::new()));
+          rollup.touch(inner_db, op, buckets)?;
+        }
+      }
+      ops = Vec::new();
+      for (_, (rollup, buckets)) in touched {
+        for start in buckets {
+          let end = rollup.bucketing.bucket(&start).and_then(|(_, end)| end);
+          let entries = overlay.read(inner_db, &rollup, self.strip_fn(), start.clone(), end)?;
+          let inner_key = build_inner_key(rollup.target_id, &start);
+          if entries.is_empty() {
+            batch.delete(inner_key);
+            ops.push(Op::Delete(rollup.target_id, start));
+          } else {
+            let value = self.build_value(&rollup, &start, &entries);
+            batch.put(inner_key, &value);
+            ops.push(Op::Put(rollup.target_id, start, value));
+          }
+        }
+      }
+    }
+    write(batch, &Routing::ByTable)
+  }
+
+  // Recomputes every bucket of the target from the source, as the source is now.
+  pub(crate) fn build_buckets(
+    &self, inner_db: &Arc<RocksdbDb>, rollup: &Rollup,
+  ) -> Result<Vec<(Bytes, Bytes)>, Error> {
+    let source = NormalTable::with_cf(inner_db.clone(), rollup.source_id, rollup.source_cf.clone());
+    let strip = self.strip_fn();
+    let mut buckets = Vec::new();
+    let mut current: Option<Bytes> = None;
+    let mut entries = Vec::new();
+    for entry in source.iter() {
+      let (key, value) = entry?;
+      let Some((start, _)) = rollup.bucketing.bucket(&key) else {
+        continue;
+      };
+      if current.as_ref() != Some(&start) {
+        if let Some(current) = current.replace(start) {
+          buckets.push((current.clone(), self.build_value(rollup, &current, &entries)));
+        }
+        entries.clear();
+      }
+      entries.push((key, strip(value)));
+    }
+    if let Some(current) = current {
+      buckets.push((current.clone(), self.build_value(rollup, &current, &entries)));
+    }
+    Ok(buckets)
+  }
+
+  #[inline]
+  fn find_by_target(
+    rollups: &AHashMap<TableId, Vec<Arc<Rollup>>>, target_id: TableId,
+  ) -> Option<&Arc<Rollup>> {
+    rollups.values().flatten().find(|r| r.target_id == target_id)
+  }
+
+  // Values of ttl dbs are stored with a header, which the merge function never sees. Corrupted
+  // values are merged as empty ones.
+  #[inline]
+  fn strip_fn(&self) -> fn(Bytes) -> Bytes {
+    match self.ttl_registry {
+      Some(_) => |value: Bytes| value.slice(extract_header_len(&value).unwrap_or(value.len())..),
+      None => |value| value,
+    }
+  }
+
+  #[inline]
+  fn build_value(&self, rollup: &Rollup, start: &[u8], entries: &[(Bytes, Bytes)]) -> Bytes {
+    let value = rollup.merge.merge(start, entries);
+    match &self.ttl_registry {
+      Some(ttl_registry) => ttl_registry.build_value(rollup.target_id, start, value),
+      None => Bytes::from(value),
+    }
+  }
+}
+
+enum Op {
+  Put(TableId, Bytes, Bytes),
+  Delete(TableId, Bytes),
+  DeleteRange(TableId, Bound<Bytes>, Bound<Bytes>),
+}
+
+impl Op {
+  #[inline]
+  fn table_id(&self) -> TableId {
+    match self {
+      Op::Put(table_id, ..) | Op::Delete(table_id, ..) | Op::DeleteRange(table_id, ..) => *table_id,
+    }
+  }
+}
+
+#[derive(Default)]
+struct OpCollector {
+  ops: Vec<Op>,
+}
+
+impl WriteBatchIterator for OpCollector {
+  #[inline]
+  fn put(&mut self, inner_key: Box<[u8]>, inner_value: Box<[u8]>) {
+    if let Some(table_id) = extract_userland_table_id(&inner_key) {
+      let key = Bytes::copy_from_slice(extract_key(&inner_key));
+      self.ops.push(Op::Put(table_id, key, Bytes::from(inner_value.into_vec())));
+    }
+  }
+
+  #[inline]
+  fn delete(&mut self, inner_key: Box<[u8]>) {
+    if let Some(table_id) = extract_userland_table_id(&inner_key) {
+      self.ops.push(Op::Delete(table_id, Bytes::copy_from_slice(extract_key(&inner_key))));
+    }
+  }
+
+  // Same as `FloorKeyChanges`, ranges bounded by anchors cover the rest of the table.
+  #[inline]
+  fn delete_range(&mut self, begin_inner_key: Box<[u8]>, end_inner_key: Box<[u8]>) {
+    if let Some(table_id) = extract_userland_table_id(&begin_inner_key) {
+      let from = match extract_key(&begin_inner_key) {
+        [] => Bound::Unbounded,
+        key => Bound::Included(Bytes::copy_from_slice(key)),
+      };
+      let to =
+        if end_inner_key.len() > TABLE_ID_LEN + 1 && extract_table_id(&end_inner_key) == table_id {
+          Bound::Excluded(Bytes::copy_from_slice(extract_key(&end_inner_key)))
+        } else {
+          Bound::Unbounded
+        };
+      self.ops.push(Op::DeleteRange(table_id, from, to));
+    }
+  }
+
+  #[inline]
+  fn merge(&mut self, _inner_key: Box<[u8]>, _inner_value: Box<[u8]>) {}
+}
+
+// The ops of a batch, which is not written yet, over what the db holds.
+#[derive(Default)]
+struct Overlay {
+  tables: AHashMap<TableId, TableOverlay>,
+}
+
+#[derive(Default)]
+struct TableOverlay {
+  entries: BTreeMap<Bytes, Option<Bytes>>,
+  deleted_ranges: Vec<(Bound<Bytes>, Bound<Bytes>)>,
+}
+
+impl Overlay {
+  fn apply(&mut self, op: &Op) {
+    let table = self.tables.entry(op.table_id()).or_default();
+    match op {
+      Op::Put(_, key, value) => {
+        table.entries.insert(key.clone(), Some(value.clone()));
+      }
+      Op::Delete(_, key) => {
+        table.entries.insert(key.clone(), None);
+      }
+      Op::DeleteRange(_, from, to) => {
+        let range = (from.clone(), to.clone());
+        let keys: Vec<_> = table.entries.range(range.clone()).map(|(key, _)| key.clone()).collect();
+        for key in keys {
+          table.entries.remove(&key);
+        }
+        table.deleted_ranges.push(range);
+      }
+    }
+  }
+
+  // Returns the entries of the bucket of the source, with the values stripped.
+  fn read(
+    &self, inner_db: &Arc<RocksdbDb>, rollup: &Rollup, strip: fn(Bytes) -> Bytes, start: Bytes,
+    end: Option<Bytes>,
+  ) -> Result<Vec<(Bytes, Bytes)>, Error> {
+    let range = (Bound::Included(start), end.map_or(Bound::Unbounded, Bound::Excluded));
+    let source = NormalTable::with_cf(inner_db.clone(), rollup.source_id, rollup.source_cf.clone());
+    let overlay = self.tables.get(&rollup.source_id);
+    let mut entries = BTreeMap::new();
+    for entry in source.range(range.clone()) {
+      let (key, value) = entry?;
+      if !overlay.is_some_and(|overlay| overlay.is_deleted(&key)) {
+        entries.insert(key, value);
+      }
+    }
+    if let Some(overlay) = overlay {
+      for (key, value) in overlay.entries.range(range) {
+        match value {
+          Some(value) => entries.insert(key.clone(), value.clone()),
+          None => entries.remove(key),
+        };
+      }
+    }
+    Ok(entries.into_iter().map(|(key, value)| (key, strip(value))).collect())
+  }
+}
+
+impl TableOverlay {
+  #[inline]
+  fn is_deleted(&self, key: &Bytes) -> bool {
+    self.deleted_ranges.iter().any(|(from, to)| {
+      let above = match from {
+        Bound::Included(from) => key >= from,
+        _ => true,
+      };
+      let below = match to {
+        Bound::Excluded(to) => key < to,
+        _ => true,
+      };
+      above && below
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_interval_secs() {
+    assert_eq!(parse_interval_secs("huobi.btc.usdt.1min"), Some(60));
+    assert_eq!(parse_interval_secs("huobi.btc.usdt.5m"), Some(300));
+    assert_eq!(parse_interval_secs("huobi.btc.usdt.4hours"), Some(14400));
+    assert_eq!(parse_interval_secs("huobi.btc.usdt.1day"), Some(86400));
+    assert_eq!(parse_interval_secs("huobi.btc.usdt.30s"), Some(30));
+    assert_eq!(parse_interval_secs("huobi.btc.usdt.min"), None);
+    assert_eq!(parse_interval_secs("huobi.btc.usdt.1mon"), None);
+    assert_eq!(parse_interval_secs("huobi.btc.usdt.5"), None);
+  }
+
+  #[test]
+  fn test_bucket() {
+    let bucketing = SecsKeyBucketing::from_table_name("huobi.btc.usdt.5min").unwrap();
+    let (start, end) = bucketing.bucket(&u32_to_u8a4(1000)).unwrap();
+    assert_eq!(&start[..], u32_to_u8a4(900));
+    assert_eq!(&end.unwrap()[..], u32_to_u8a4(1200));
+    assert!(bucketing.bucket(b"k").is_none());
+    let bucketing = MillisKeyBucketing(60_000);
+    let (start, _) = bucketing.bucket(&u64_to_u8a8(61_000)).unwrap();
+    assert_eq!(&start[..], u64_to_u8a8(60_000));
+  }
+}
@@ -0,0 +1,46 @@
+/// The statistics of the values of a time bucket, as projected to `f64`. `first` and `last` are
+/// the values of the lowest and highest keys of the bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketStats {
+  pub count: u64,
+  pub sum: f64,
+  pub min: f64,
+  pub max: f64,
+  pub first: f64,
+  pub last: f64,
+}
+
+impl Default for BucketStats {
+  #[inline]
+  fn default() -> Self {
+    BucketStats {
+      count: 0,
+      sum: 0.0,
+      min: f64::INFINITY,
+      max: f64::NEG_INFINITY,
+      first: f64::NAN,
+      last: f64::NAN,
+    }
+  }
+}
+
+impl BucketStats {
+  /// Values are to be added in key order.
+  #[inline]
+  pub fn add(&mut self, value: f64) {
+    if self.count == 0 {
+      self.first = value;
+    }
+    self.count += 1;
+    self.sum += value;
+    self.min = self.min.min(value);
+    self.max = self.max.max(value);
+    self.last = value;
+  }
+}
+
+/// Returns the start of the bucket of the given width holding the timestamp.
+#[inline]
+pub(crate) fn bucket_start(timestamp: u64, width: u64) -> u64 {
+  timestamp - timestamp % width
+}
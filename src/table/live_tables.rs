@@ -0,0 +1,85 @@
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, Weak};
+
+use ahash::AHashMap;
+
+use crate::error::Error;
+use crate::types::*;
+use crate::utils::*;
+
+/// Whether a table is still alive, shared by all of its handles. Writes hold it for reading, so
+/// none of them lands after the table is destroyed.
+#[derive(Debug)]
+pub struct Liveness(RwLock<bool>);
+
+impl Default for Liveness {
+  fn default() -> Self {
+    Liveness(RwLock::new(true))
+  }
+}
+
+impl Liveness {
+  #[inline]
+  pub fn is_alive(&self) -> bool {
+    *self.0.read().unwrap()
+  }
+
+  #[inline]
+  pub(crate) fn ensure_alive(&self, id: TableId) -> Result<RwLockReadGuard<'_, bool>, Error> {
+    let alive = self.0.read().unwrap();
+    if *alive {
+      Ok(alive)
+    } else {
+      Err(Error::TableDestroyed(u8a4_to_u32(id)))
+    }
+  }
+}
+
+/// Hands out the liveness of the tables of a db.
+#[derive(Default)]
+pub struct LiveTables {
+  livenesses: Mutex<AHashMap<TableId, Weak<Liveness>>>,
+}
+
+impl LiveTables {
+  pub(crate) fn get(&self, id: TableId) -> Arc<Liveness> {
+    let mut livenesses = self.livenesses.lock().unwrap();
+    if let Some(liveness) = livenesses.get(&id).and_then(Weak::upgrade) {
+      return liveness;
+    }
+    let liveness = Arc::new(Liveness::default());
+    livenesses.insert(id, Arc::downgrade(&liveness));
+    liveness
+  }
+
+  // Runs the write destroying the tables once no other write to them is in flight, then marks
+  // their handles dead. Handles got afterwards are alive, for the ids may be reused.
+  pub(crate) fn destroy<F>(&self, ids: &[TableId], write: F) -> Result<(), Error>
+  where F: FnOnce() -> Result<(), Error> {
+    let livenesses: Vec<_> = ids.iter().map(|id| self.get(*id)).collect();
+    let mut alives: Vec<_> =
+      livenesses.iter().map(|liveness| liveness.0.write().unwrap()).collect();
+    write()?;
+    let mut livenesses = self.livenesses.lock().unwrap();
+    for (id, alive) in ids.iter().zip(alives.iter_mut()) {
+      **alive = false;
+      livenesses.remove(id);
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_destroy() {
+    let live_tables = LiveTables::default();
+    let liveness = live_tables.get([0, 0, 4, 0]);
+    assert!(Arc::ptr_eq(&liveness, &live_tables.get([0, 0, 4, 0])));
+    assert!(live_tables.destroy(&[[0, 0, 4, 0]], || Ok(())).is_ok());
+    assert!(!liveness.is_alive());
+    assert!(matches!(liveness.ensure_alive([0, 0, 4, 0]), Err(Error::TableDestroyed(1024))));
+    assert!(live_tables.get([0, 0, 4, 0]).is_alive());
+  }
+}
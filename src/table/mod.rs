@@ -1,9 +1,21 @@
+pub mod bucket;
+pub mod live_tables;
 pub mod normal_table;
+pub mod page;
+pub mod range_iterator;
+pub mod range_iterator_enhanced;
 pub mod table;
 pub mod table_enhanced;
+pub mod table_meta;
 pub mod ttl_table;
 
+pub use bucket::*;
+pub use live_tables::*;
 pub use normal_table::*;
+pub use page::*;
+pub use range_iterator::*;
+pub use range_iterator_enhanced::*;
 pub use table::*;
 pub use table_enhanced::*;
+pub use table_meta::*;
 pub use ttl_table::*;
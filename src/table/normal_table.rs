@@ -5,9 +5,12 @@ use bytes::Bytes;
 use rocksdb::ReadOptions;
 use rocksdb::DB as RocksdbDb;
 
+use super::live_tables::Liveness;
 use super::table::Table;
+use crate::column_family::ColumnFamily;
 use crate::cursor::*;
 use crate::error::Error;
+use crate::rollup::RollupRegistry;
 use crate::types::*;
 use crate::utils::*;
 use crate::write_batch::*;
@@ -17,6 +20,9 @@ pub struct NormalTable {
   pub(crate) inner_db: Arc<RocksdbDb>,
   pub(crate) id: TableId,
   pub(crate) tail_anchor: Bytes,
+  pub(crate) cf: ColumnFamily,
+  pub(crate) liveness: Arc<Liveness>,
+  pub(crate) rollups: Arc<RollupRegistry>,
 }
 
 impl fmt::Debug for NormalTable {
@@ -34,41 +40,87 @@ impl Table for NormalTable {
     self.id
   }
 
+  #[inline]
+  fn group(&self) -> &str {
+    self.cf.group()
+  }
+
+  #[inline]
+  fn is_alive(&self) -> bool {
+    self.liveness.is_alive()
+  }
+
   #[inline]
   fn put<K, V>(&self, key: K, value: V) -> Result<(), Error>
   where
     K: AsRef<[u8]>,
     V: AsRef<[u8]>, {
-    Ok(self.inner_db.put(build_inner_key(self.id, key), value)?)
+    if self.rollups.is_source(self.id) {
+      let mut batch = self.new_write_batch();
+      batch.put(key, value);
+      return batch.write();
+    }
+    let _alive = self.liveness.ensure_alive(self.id)?;
+    Ok(self.cf.put(&self.inner_db, build_inner_key(self.id, key), value)?)
   }
 
   #[inline]
   fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<(), Error> {
-    Ok(self.inner_db.delete(build_inner_key(self.id, key))?)
+    if self.rollups.is_source(self.id) {
+      let mut batch = self.new_write_batch();
+      batch.delete(key);
+      return batch.write();
+    }
+    let _alive = self.liveness.ensure_alive(self.id)?;
+    Ok(self.cf.delete(&self.inner_db, build_inner_key(self.id, key))?)
   }
 
   #[inline]
   fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Bytes>, Error> {
-    Ok(self.inner_db.get(build_inner_key(self.id, key))?.map(|value| Bytes::from(value)))
+    let _alive = self.liveness.ensure_alive(self.id)?;
+    Ok(self.cf.get(&self.inner_db, build_inner_key(self.id, key))?.map(|value| Bytes::from(value)))
   }
 
   #[inline]
   fn new_write_batch(&self) -> Self::WriteBatch {
-    NormalWriteBatch::new(self.inner_db.clone(), self.id)
+    NormalWriteBatch::with_liveness(
+      self.inner_db.clone(),
+      self.id,
+      self.cf.clone(),
+      self.liveness.clone(),
+      self.rollups.clone(),
+    )
   }
 
   #[inline]
   fn new_cursor<'a>(&'a self) -> Self::Cursor<'a> {
     let mut opts = ReadOptions::default();
     opts.set_prefix_same_as_start(true);
-    NormalCursor::new(self.inner_db.raw_iterator_opt(opts), self.id, &self.tail_anchor)
+    let mut cursor =
+      NormalCursor::new(self.cf.raw_iterator_opt(&self.inner_db, opts), self.id, &self.tail_anchor);
+    // The id of a destroyed table may already be reused by another one.
+    cursor.table_destroyed = !self.liveness.is_alive();
+    cursor
   }
 }
 
 impl NormalTable {
   #[inline]
   pub(crate) fn new(inner_db: Arc<RocksdbDb>, id: TableId) -> Self {
-    NormalTable { inner_db, id, tail_anchor: build_tail_anchor(id) }
+    Self::with_cf(inner_db, id, ColumnFamily::default())
+  }
+
+  #[inline]
+  pub(crate) fn with_cf(inner_db: Arc<RocksdbDb>, id: TableId, cf: ColumnFamily) -> Self {
+    Self::with_liveness(inner_db, id, cf, Arc::default(), Arc::default())
+  }
+
+  #[inline]
+  pub(crate) fn with_liveness(
+    inner_db: Arc<RocksdbDb>, id: TableId, cf: ColumnFamily, liveness: Arc<Liveness>,
+    rollups: Arc<RollupRegistry>,
+  ) -> Self {
+    NormalTable { inner_db, id, tail_anchor: build_tail_anchor(id), cf, liveness, rollups }
   }
 }
 
@@ -0,0 +1,132 @@
+use std::{fmt, str::FromStr};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::error::Error;
+
+const PAGE_TOKEN_VERSION: u8 = 1;
+
+/// The order in which a query visits the keys of a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  Forward = 0,
+  Backward = 1,
+}
+
+/// A page of entries, in the order of the query, and the key the next page starts at, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<K, V> {
+  pub entries: Vec<(K, V)>,
+  pub next_key: Option<K>,
+}
+
+impl<K, V> Page<K, V> {
+  // Reads one entry past the limit to tell where the next page starts.
+  pub(crate) fn collect<I>(iter: I, limit: u32) -> Result<Self, Error>
+  where I: Iterator<Item = Result<(K, V), Error>> {
+    let mut entries = Vec::new();
+    for entry in iter {
+      let (key, value) = entry?;
+      if entries.len() == limit as usize {
+        return Ok(Page { entries, next_key: Some(key) });
+      }
+      entries.push((key, value));
+    }
+    Ok(Page { entries, next_key: None })
+  }
+}
+
+/// The entries of a page, and the token resuming the query after it, if any.
+pub type TokenPage<K, V> = (Vec<(K, V)>, Option<PageToken>);
+
+/// An opaque position to resume a query at, i.e. the direction of the query and the encoded key
+/// the next page starts at. It holds nothing specific to the process, so it can be handed out, as
+/// bytes or as a hex string, and be used after a restart.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageToken(Bytes);
+
+impl PageToken {
+  #[inline]
+  pub(crate) fn new(direction: Direction, key: &[u8]) -> Self {
+    let mut buf = BytesMut::with_capacity(2 + key.len());
+    buf.put_u8(PAGE_TOKEN_VERSION);
+    buf.put_u8(direction as u8);
+    buf.put_slice(key);
+    PageToken(buf.freeze())
+  }
+
+  /// Returns `Error::InvalidPageToken` if the bytes are not those of a token.
+  #[inline]
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+    match bytes {
+      [PAGE_TOKEN_VERSION, 0 | 1, ..] => Ok(PageToken(Bytes::copy_from_slice(bytes))),
+      _ => Err(Error::InvalidPageToken(bytes.to_vec())),
+    }
+  }
+
+  #[inline]
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.0
+  }
+
+  #[inline]
+  pub fn direction(&self) -> Direction {
+    if self.0[1] == Direction::Forward as u8 {
+      Direction::Forward
+    } else {
+      Direction::Backward
+    }
+  }
+
+  #[inline]
+  pub(crate) fn key(&self) -> &[u8] {
+    &self.0[2..]
+  }
+}
+
+impl fmt::Display for PageToken {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for byte in self.0.iter() {
+      write!(f, "{:02x}", byte)?;
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for PageToken {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let bytes = s
+      .as_bytes()
+      .chunks(2)
+      .map(|pair| match std::str::from_utf8(pair) {
+        Ok(pair) if pair.len() == 2 && pair.bytes().all(|b| b.is_ascii_hexdigit()) => {
+          u8::from_str_radix(pair, 16).ok()
+        }
+        _ => None,
+      })
+      .collect::<Option<Vec<u8>>>()
+      .ok_or_else(|| Error::InvalidPageToken(s.as_bytes().to_vec()))?;
+    Self::from_bytes(&bytes)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_page_token() {
+    let token = PageToken::new(Direction::Backward, &[0, 0, 1, 255]);
+    assert_eq!(token.direction(), Direction::Backward);
+    assert_eq!(token.key(), [0, 0, 1, 255]);
+    assert_eq!(token.to_string(), "0101000001ff");
+    assert_eq!(token.to_string().parse::<PageToken>().unwrap(), token);
+    assert_eq!(PageToken::from_bytes(token.as_bytes()).unwrap(), token);
+    assert!(matches!(PageToken::from_bytes(&[1, 2]), Err(Error::InvalidPageToken(_))));
+    assert!(matches!(PageToken::from_bytes(&[]), Err(Error::InvalidPageToken(_))));
+    assert!(matches!("0100f".parse::<PageToken>(), Err(Error::InvalidPageToken(_))));
+    assert!(matches!("0100zz".parse::<PageToken>(), Err(Error::InvalidPageToken(_))));
+  }
+}
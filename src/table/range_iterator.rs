@@ -0,0 +1,192 @@
+use std::ops::{Bound, RangeBounds};
+
+use bytes::Bytes;
+
+use super::table::Table;
+use crate::cursor::*;
+use crate::error::Error;
+
+/// Iterates the keys and values of a table within a range, in key order, or in reverse order from
+/// the back end. Each end seeks a cursor of its own on its first use, and the ends never cross.
+pub struct RangeIterator<'a, T: Table + 'a> {
+  table: &'a T,
+  lower: Bound<Bytes>,
+  upper: Bound<Bytes>,
+  front: Option<T::Cursor<'a>>,
+  back: Option<T::Cursor<'a>>,
+  done: bool,
+}
+
+impl<'a, T: Table + 'a> Iterator for RangeIterator<'a, T> {
+  type Item = Result<(Bytes, Bytes), Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let cursor = match &mut self.front {
+      Some(cursor) => {
+        cursor.next();
+        cursor
+      }
+      None => {
+        let mut cursor = self.table.new_cursor();
+        match &self.lower {
+          Bound::Included(key) => cursor.seek(key),
+          Bound::Excluded(key) => {
+            cursor.seek(key);
+            if cursor.key() == Some(&key[..]) {
+              cursor.next();
+            }
+          }
+          Bound::Unbounded => cursor.seek_to_first(),
+        }
+        self.front.insert(cursor)
+      }
+    };
+    let result = Self::read(cursor, |key| is_below(key, &self.upper));
+    if let Some(Ok((key, _))) = &result {
+      self.lower = Bound::Excluded(key.clone());
+    } else {
+      self.done = true;
+    }
+    result
+  }
+}
+
+impl<'a, T: Table + 'a> DoubleEndedIterator for RangeIterator<'a, T> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let cursor = match &mut self.back {
+      Some(cursor) => {
+        cursor.prev();
+        cursor
+      }
+      None => {
+        let mut cursor = self.table.new_cursor();
+        match &self.upper {
+          Bound::Included(key) => cursor.seek_for_prev(key),
+          Bound::Excluded(key) => {
+            cursor.seek_for_prev(key);
+            if cursor.key() == Some(&key[..]) {
+              cursor.prev();
+            }
+          }
+          Bound::Unbounded => cursor.seek_to_last(),
+        }
+        self.back.insert(cursor)
+      }
+    };
+    let result = Self::read(cursor, |key| is_above(key, &self.lower));
+    if let Some(Ok((key, _))) = &result {
+      self.upper = Bound::Excluded(key.clone());
+    } else {
+      self.done = true;
+    }
+    result
+  }
+}
+
+impl<'a, T: Table + 'a> RangeIterator<'a, T> {
+  pub(crate) fn new<K: AsRef<[u8]>, R: RangeBounds<K>>(table: &'a T, range: R) -> Self {
+    RangeIterator {
+      table,
+      lower: to_owned_bound(range.start_bound()),
+      upper: to_owned_bound(range.end_bound()),
+      front: None,
+      back: None,
+      done: false,
+    }
+  }
+
+  // Returns the entry under the cursor if it is within the range, as told by `is_within`.
+  #[inline]
+  fn read(
+    cursor: &T::Cursor<'a>, is_within: impl Fn(&[u8]) -> bool,
+  ) -> Option<Result<(Bytes, Bytes), Error>> {
+    if !cursor.is_valid() {
+      return cursor.status().err().map(Err);
+    }
+    let key = cursor.key().unwrap();
+    if !is_within(key) {
+      return None;
+    }
+    Some(Ok((Bytes::copy_from_slice(key), Bytes::copy_from_slice(cursor.value().unwrap()))))
+  }
+}
+
+#[inline]
+pub(crate) fn to_owned_bound<K: AsRef<[u8]>>(bound: Bound<&K>) -> Bound<Bytes> {
+  match bound {
+    Bound::Included(key) => Bound::Included(Bytes::copy_from_slice(key.as_ref())),
+    Bound::Excluded(key) => Bound::Excluded(Bytes::copy_from_slice(key.as_ref())),
+    Bound::Unbounded => Bound::Unbounded,
+  }
+}
+
+#[inline]
+pub(crate) fn is_below(key: &[u8], upper: &Bound<Bytes>) -> bool {
+  match upper {
+    Bound::Included(upper) => key <= &upper[..],
+    Bound::Excluded(upper) => key < &upper[..],
+    Bound::Unbounded => true,
+  }
+}
+
+#[inline]
+pub(crate) fn is_above(key: &[u8], lower: &Bound<Bytes>) -> bool {
+  match lower {
+    Bound::Included(lower) => key >= &lower[..],
+    Bound::Excluded(lower) => key > &lower[..],
+    Bound::Unbounded => true,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::ops::Bound;
+
+  use bytes::Bytes;
+
+  use crate::db::*;
+  use crate::error::Error;
+  use crate::setup;
+  use crate::table::*;
+
+  #[test]
+  fn test_range() {
+    setup!("range_iterator.test_range"; db);
+    let table = db.open_table("huobi.btc.usdt.1m").unwrap();
+    db.open_table("huobi.btc.usdt.3m").unwrap().put(b"k0", b"v0").unwrap();
+    for key in [b"k1", b"k2", b"k3", b"k4"] {
+      table.put(key, key).unwrap();
+    }
+
+    fn keys(iter: impl Iterator<Item = Result<(Bytes, Bytes), Error>>) -> Vec<Bytes> {
+      iter.map(|entry| entry.unwrap().0).collect()
+    }
+    assert_eq!(keys(table.iter()), [&b"k1"[..], b"k2", b"k3", b"k4"]);
+    assert_eq!(keys(table.range(&b"k2"[..]..&b"k4"[..])), [&b"k2"[..], b"k3"]);
+    assert_eq!(keys(table.range(&b"k2"[..]..=&b"k4"[..])), [&b"k2"[..], b"k3", b"k4"]);
+    assert_eq!(
+      keys(table.range::<&[u8], _>((Bound::Excluded(&b"k2"[..]), Bound::Unbounded))),
+      [&b"k3"[..], b"k4"]
+    );
+    assert_eq!(keys(table.range(..&b"k25"[..])), [&b"k1"[..], b"k2"]);
+    assert!(keys(table.range(&b"k5"[..]..)).is_empty());
+
+    let entries: Vec<_> = table.iter().rev().map(|entry| entry.unwrap()).collect();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0], (Bytes::from("k4"), Bytes::from("k4")));
+    assert_eq!(keys(table.range(..=&b"k3"[..]).rev()), [&b"k3"[..], b"k2", b"k1"]);
+
+    let mut iter = table.range(&b"k1"[..]..&b"k4"[..]);
+    assert_eq!(iter.next().unwrap().unwrap().0, "k1");
+    assert_eq!(iter.next_back().unwrap().unwrap().0, "k3");
+    assert_eq!(iter.next().unwrap().unwrap().0, "k2");
+    assert!(iter.next_back().is_none());
+    assert!(iter.next().is_none());
+  }
+}
@@ -0,0 +1,54 @@
+use std::marker::PhantomData;
+use std::ops::Bound;
+
+use bytes::Bytes;
+
+use super::range_iterator::RangeIterator;
+use super::table::Table;
+use crate::coder::*;
+use crate::error::Error;
+
+pub struct RangeIteratorEnhanced<'a, T: Table + 'a, K, V, C: Coder<K, V>> {
+  pub(crate) raw: RangeIterator<'a, T>,
+  phantom: PhantomData<(K, V, C)>,
+}
+
+impl<'a, T: Table + 'a, K, V, C: Coder<K, V>> Iterator for RangeIteratorEnhanced<'a, T, K, V, C> {
+  type Item = Result<(K, V), Error>;
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    self.raw.next().map(|result| result.map(Self::decode))
+  }
+}
+
+impl<'a, T: Table + 'a, K, V, C: Coder<K, V>> DoubleEndedIterator
+  for RangeIteratorEnhanced<'a, T, K, V, C>
+{
+  #[inline]
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.raw.next_back().map(|result| result.map(Self::decode))
+  }
+}
+
+impl<'a, T: Table + 'a, K, V, C: Coder<K, V>> RangeIteratorEnhanced<'a, T, K, V, C> {
+  #[inline]
+  pub fn new(raw: RangeIterator<'a, T>) -> Self {
+    Self { raw, phantom: PhantomData }
+  }
+
+  #[inline]
+  fn decode((key, value): (Bytes, Bytes)) -> (K, V) {
+    (C::decode_key(&key), C::decode_value(&value))
+  }
+}
+
+// Keys are encoded in an order preserving way, so bounds on them carry over to the encoded keys.
+#[inline]
+pub(crate) fn encode_bound<K, V, C: Coder<K, V>>(bound: Bound<&K>) -> Bound<C::EncodedKey> {
+  match bound {
+    Bound::Included(key) => Bound::Included(C::encode_key(key)),
+    Bound::Excluded(key) => Bound::Excluded(C::encode_key(key)),
+    Bound::Unbounded => Bound::Unbounded,
+  }
+}
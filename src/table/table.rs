@@ -1,8 +1,8 @@
-use std::{cmp::Ord, sync::Arc};
+use std::{cmp::Ord, ops::RangeBounds, sync::Arc};
 
 use bytes::Bytes;
 
-use super::TableEnhanced;
+use super::{RangeIterator, TableEnhanced};
 use crate::coder::Coder;
 use crate::cursor::Cursor;
 use crate::error::Error;
@@ -16,6 +16,12 @@ pub trait Table {
 
   fn id(&self) -> TableId;
 
+  /// Returns the group of the table, whose column family holds its data.
+  fn group(&self) -> &str;
+
+  /// Returns false once the table is destroyed, after which its operations fail.
+  fn is_alive(&self) -> bool;
+
   fn put<K, V>(&self, key: K, value: V) -> Result<(), Error>
   where
     K: AsRef<[u8]>,
@@ -38,6 +44,20 @@ pub trait Table {
 
   fn new_cursor<'a>(&'a self) -> Self::Cursor<'a>;
 
+  /// Iterates the entries whose keys are within the range, in key order, or in reverse order once
+  /// reversed.
+  #[inline]
+  fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> RangeIterator<'_, Self>
+  where Self: Sized {
+    RangeIterator::new(self, range)
+  }
+
+  #[inline]
+  fn iter(&self) -> RangeIterator<'_, Self>
+  where Self: Sized {
+    RangeIterator::new::<&[u8], _>(self, ..)
+  }
+
   #[inline]
   fn enhance<K: Ord, V, C: Coder<K, V>>(self: Arc<Self>) -> TableEnhanced<Self, K, V, C>
   where Self: Sized {
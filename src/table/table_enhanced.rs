@@ -1,6 +1,19 @@
-use std::{borrow::Borrow, marker::PhantomData, sync::Arc};
-
+use std::{
+  borrow::Borrow,
+  collections::BTreeMap,
+  marker::PhantomData,
+  ops::{Bound, RangeBounds},
+  sync::Arc,
+};
+
+use bytes::Bytes;
+
+use super::bucket::*;
+use super::page::*;
+use super::range_iterator::*;
+use super::range_iterator_enhanced::*;
 use super::table::Table;
+use super::ttl_table::TtlTable;
 use crate::coder::*;
 use crate::cursor::*;
 use crate::error::Error;
@@ -142,6 +155,119 @@ impl<T: Table, K, V, C: Coder<K, V>> TableEnhanced<T, K, V, C> {
     values
   }
 
+  /// Same as `get_since`, but returns the keys too.
+  #[inline]
+  pub fn get_entries_since<BK: Borrow<K>>(
+    &self, key: BK, limit: u32,
+  ) -> Result<Vec<(K, V)>, Error> {
+    let range = (Bound::Included(C::encode_key(key)), Bound::Unbounded);
+    Ok(self.query_encoded(range, Direction::Forward, limit)?.entries)
+  }
+
+  /// Same as `get_until`, but returns the keys too.
+  #[inline]
+  pub fn get_entries_until<BK: Borrow<K>>(
+    &self, key: BK, limit: u32,
+  ) -> Result<Vec<(K, V)>, Error> {
+    let range = (Bound::Unbounded, Bound::Included(C::encode_key(key)));
+    let mut entries = self.query_encoded(range, Direction::Backward, limit)?.entries;
+    entries.reverse();
+    Ok(entries)
+  }
+
+  /// Returns up to `limit` entries whose keys are within the range, visited in the given
+  /// direction, along with the key the next page starts at. That is, the next page is the range
+  /// narrowed to start, or end when going backward, at that key, inclusively.
+  #[inline]
+  pub fn query<R: RangeBounds<K>>(
+    &self, range: R, direction: Direction, limit: u32,
+  ) -> Result<Page<K, V>, Error> {
+    let range =
+      (encode_bound::<K, V, C>(range.start_bound()), encode_bound::<K, V, C>(range.end_bound()));
+    self.query_encoded(range, direction, limit)
+  }
+
+  /// Same as `query`, but pages are chained with tokens instead of keys. The token returned, if
+  /// any, resumes the query right where the page ends, given the same range and direction.
+  /// Returns `Error::InvalidPageToken` if the token is of the other direction or out of the range.
+  #[inline]
+  pub fn query_with_token<R: RangeBounds<K>>(
+    &self, range: R, direction: Direction, limit: u32, token: Option<&PageToken>,
+  ) -> Result<TokenPage<K, V>, Error> {
+    let mut lower = to_owned_bound(encode_bound::<K, V, C>(range.start_bound()).as_ref());
+    let mut upper = to_owned_bound(encode_bound::<K, V, C>(range.end_bound()).as_ref());
+    if let Some(token) = token {
+      let key = token.key();
+      if token.direction() != direction || !is_above(key, &lower) || !is_below(key, &upper) {
+        return Err(Error::InvalidPageToken(token.as_bytes().to_vec()));
+      }
+      let resume = Bound::Included(Bytes::copy_from_slice(key));
+      match direction {
+        Direction::Forward => lower = resume,
+        Direction::Backward => upper = resume,
+      }
+    }
+    let iter = RangeIterator::new(self.raw.as_ref(), (lower, upper));
+    let page = match direction {
+      Direction::Forward => Page::collect(iter, limit)?,
+      Direction::Backward => Page::collect(iter.rev(), limit)?,
+    };
+    let entries = page
+      .entries
+      .into_iter()
+      .map(|(key, value)| (C::decode_key(&key), C::decode_value(&value)))
+      .collect();
+    Ok((entries, page.next_key.map(|key| PageToken::new(direction, &key))))
+  }
+
+  #[inline]
+  fn query_encoded(
+    &self, range: (Bound<C::EncodedKey>, Bound<C::EncodedKey>), direction: Direction, limit: u32,
+  ) -> Result<Page<K, V>, Error> {
+    let iter =
+      RangeIteratorEnhanced::<T, K, V, C>::new(RangeIterator::new(self.raw.as_ref(), range));
+    match direction {
+      Direction::Forward => Page::collect(iter, limit),
+      Direction::Backward => Page::collect(iter.rev(), limit),
+    }
+  }
+
+  /// Groups the entries whose keys are within the range into buckets of the given width, by the
+  /// timestamps of their keys, and returns the statistics of the values of each bucket, as
+  /// projected to `f64`. Buckets are keyed by their start and sorted, and empty ones are left out.
+  /// Returns `Error::InvalidBucketWidth` if the width is 0.
+  #[inline]
+  pub fn aggregate<R: RangeBounds<K>>(
+    &self, range: R, width: u64, timestamp_of: impl Fn(&K) -> u64, project: impl Fn(&V) -> f64,
+  ) -> Result<Vec<(u64, BucketStats)>, Error> {
+    self.fold_buckets(
+      range,
+      width,
+      timestamp_of,
+      |_| BucketStats::default(),
+      |stats, _, value| stats.add(project(value)),
+    )
+  }
+
+  /// Same as `aggregate`, but each bucket is folded by the given closures instead, starting from
+  /// what `init` returns for the start of the bucket. Entries are folded in key order.
+  #[inline]
+  pub fn fold_buckets<R: RangeBounds<K>, A>(
+    &self, range: R, width: u64, timestamp_of: impl Fn(&K) -> u64, init: impl Fn(u64) -> A,
+    mut fold: impl FnMut(&mut A, &K, &V),
+  ) -> Result<Vec<(u64, A)>, Error> {
+    if width == 0 {
+      return Err(Error::InvalidBucketWidth(width));
+    }
+    let mut buckets = BTreeMap::new();
+    for entry in self.range(range) {
+      let (key, value) = entry?;
+      let start = bucket_start(timestamp_of(&key), width);
+      fold(buckets.entry(start).or_insert_with(|| init(start)), &key, &value);
+    }
+    Ok(buckets.into_iter().collect())
+  }
+
   #[inline]
   pub fn get_first_key(&self) -> Option<K> {
     let mut cursor = self.new_cursor();
@@ -178,6 +304,20 @@ impl<T: Table, K, V, C: Coder<K, V>> TableEnhanced<T, K, V, C> {
     }
   }
 
+  /// Iterates the entries whose keys are within the range, in key order, or in reverse order once
+  /// reversed.
+  #[inline]
+  pub fn range<R: RangeBounds<K>>(&self, range: R) -> RangeIteratorEnhanced<'_, T, K, V, C> {
+    let range =
+      (encode_bound::<K, V, C>(range.start_bound()), encode_bound::<K, V, C>(range.end_bound()));
+    RangeIteratorEnhanced::new(RangeIterator::new(self.raw.as_ref(), range))
+  }
+
+  #[inline]
+  pub fn iter(&self) -> RangeIteratorEnhanced<'_, T, K, V, C> {
+    RangeIteratorEnhanced::new(self.raw.iter())
+  }
+
   #[inline]
   pub fn new_write_batch(&self) -> WriteBatchEnhanced<T::WriteBatch, K, V, C> {
     self.raw.new_write_batch().enhance()
@@ -189,6 +329,40 @@ impl<T: Table, K, V, C: Coder<K, V>> TableEnhanced<T, K, V, C> {
   }
 }
 
+impl<K, V, C: Coder<K, V>> TableEnhanced<TtlTable, K, V, C> {
+  /// Puts a value which expires at `expires_at_millis`, a unix time in milliseconds, instead of
+  /// after the table's ttl.
+  #[inline]
+  pub fn put_with_expiry<BK: Borrow<K>, BV: Borrow<V>>(
+    &self, key: BK, value: BV, expires_at_millis: u64,
+  ) -> Result<(), Error> {
+    self.raw.put_with_expiry(C::encode_key(key), C::encode_value(value), expires_at_millis)
+  }
+
+  /// Puts a value which expires `ttl_secs` seconds from now instead of after the table's ttl.
+  #[inline]
+  pub fn put_with_ttl<BK: Borrow<K>, BV: Borrow<V>>(
+    &self, key: BK, value: BV, ttl_secs: u32,
+  ) -> Result<(), Error> {
+    self.raw.put_with_ttl(C::encode_key(key), C::encode_value(value), ttl_secs)
+  }
+
+  #[inline]
+  pub fn get_including_expired<BK: Borrow<K>>(&self, key: BK) -> Result<Option<V>, Error> {
+    Ok(
+      self
+        .raw
+        .get_including_expired(C::encode_key(key))?
+        .map(|value| C::decode_value(value.as_ref())),
+    )
+  }
+
+  #[inline]
+  pub fn new_cursor_including_expired<'a>(&'a self) -> CursorEnhanced<TtlCursor<'a>, K, V, C> {
+    self.raw.new_cursor_including_expired().enhance()
+  }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -199,8 +373,10 @@ mod tests {
 
   use crate::coder::Coder as SeriesdbCoder;
   use crate::db::*;
+  use crate::error::Error;
   use crate::table::*;
   use crate::types::*;
+  use crate::utils::now;
   use crate::{setup, setup_with_ttl};
 
   type Key = u32;
@@ -378,6 +554,137 @@ mod tests {
     assert_eq!(table.get_between(0, k2, 3), vec![&v1, &v2]);
   }
 
+  #[test]
+  fn test_range() {
+    setup!("table_enhanced.range"; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap().enhance::<Key, Value, Coder>();
+
+    for k in 1..=5 {
+      table.put(k, Bytes::from(k.to_string())).unwrap();
+    }
+
+    let keys: Vec<Key> = table.range(2..4).map(|entry| entry.unwrap().0).collect();
+    assert_eq!(keys, vec![2, 3]);
+    let keys: Vec<Key> = table.range(2..=4).rev().map(|entry| entry.unwrap().0).collect();
+    assert_eq!(keys, vec![4, 3, 2]);
+    let keys: Vec<Key> = table.range(..2).map(|entry| entry.unwrap().0).collect();
+    assert_eq!(keys, vec![1]);
+    let entries: Vec<(Key, Value)> = table.iter().rev().take(2).map(Result::unwrap).collect();
+    assert_eq!(entries, vec![(5, Bytes::from("5")), (4, Bytes::from("4"))]);
+    assert_eq!(table.range(6..).count(), 0);
+  }
+
+  #[test]
+  fn test_query() {
+    setup!("table_enhanced.query"; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap().enhance::<Key, Value, Coder>();
+
+    for k in 1..=5 {
+      table.put(k, Bytes::from(k.to_string())).unwrap();
+    }
+
+    let page = table.query(2..5, Direction::Forward, 2).unwrap();
+    assert_eq!(page.entries, vec![(2, Bytes::from("2")), (3, Bytes::from("3"))]);
+    assert_eq!(page.next_key, Some(4));
+    let page = table.query(page.next_key.unwrap()..5, Direction::Forward, 2).unwrap();
+    assert_eq!(page.entries, vec![(4, Bytes::from("4"))]);
+    assert!(page.next_key.is_none());
+
+    let page = table.query(..=4, Direction::Backward, 3).unwrap();
+    let keys: Vec<Key> = page.entries.iter().map(|(k, _)| *k).collect();
+    assert_eq!(keys, vec![4, 3, 2]);
+    assert_eq!(page.next_key, Some(1));
+    let page = table.query(..=5, Direction::Backward, 0).unwrap();
+    assert!(page.entries.is_empty());
+    assert_eq!(page.next_key, Some(5));
+
+    let entries = table.get_entries_since(4, 3).unwrap();
+    assert_eq!(entries, vec![(4, Bytes::from("4")), (5, Bytes::from("5"))]);
+    let entries = table.get_entries_until(2, 3).unwrap();
+    assert_eq!(entries, vec![(1, Bytes::from("1")), (2, Bytes::from("2"))]);
+  }
+
+  #[test]
+  fn test_query_with_token() {
+    setup!("table_enhanced.query_with_token"; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap().enhance::<Key, Value, Coder>();
+
+    for k in 1..=5 {
+      table.put(k, Bytes::from(k.to_string())).unwrap();
+    }
+
+    let mut keys = Vec::new();
+    let mut token: Option<PageToken> = None;
+    loop {
+      let (entries, next) =
+        table.query_with_token(2.., Direction::Forward, 2, token.as_ref()).unwrap();
+      keys.extend(entries.into_iter().map(|(k, _)| k));
+      // Round trips through a string, as if handed to a client.
+      match next {
+        Some(next) => token = Some(next.to_string().parse().unwrap()),
+        None => break,
+      }
+    }
+    assert_eq!(keys, vec![2, 3, 4, 5]);
+
+    let (entries, token) = table.query_with_token(..5, Direction::Backward, 3, None).unwrap();
+    assert_eq!(entries.iter().map(|(k, _)| *k).collect::<Vec<_>>(), vec![4, 3, 2]);
+    let token = token.unwrap();
+    assert_eq!(token.direction(), Direction::Backward);
+    let (entries, next) =
+      table.query_with_token(..5, Direction::Backward, 3, Some(&token)).unwrap();
+    assert_eq!(entries, vec![(1, Bytes::from("1"))]);
+    assert!(next.is_none());
+    assert!(matches!(
+      table.query_with_token(..5, Direction::Forward, 3, Some(&token)),
+      Err(Error::InvalidPageToken(_))
+    ));
+    assert!(matches!(
+      table.query_with_token(2..5, Direction::Backward, 3, Some(&token)),
+      Err(Error::InvalidPageToken(_))
+    ));
+  }
+
+  #[test]
+  fn test_aggregate() {
+    setup!("table_enhanced.aggregate"; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap().enhance::<Key, Value, Coder>();
+
+    for k in 1..=10 {
+      table.put(k, Bytes::from(k.to_string())).unwrap();
+    }
+
+    let timestamp_of = |key: &Key| *key as u64 * 1000;
+    let project = |value: &Value| std::str::from_utf8(value).unwrap().parse::<f64>().unwrap();
+    let buckets = table.aggregate(2..=9, 4000, timestamp_of, project).unwrap();
+    let stats = |count, sum, min, max| BucketStats { count, sum, min, max, first: min, last: max };
+    assert_eq!(
+      buckets,
+      vec![
+        (0, stats(2, 5.0, 2.0, 3.0)),
+        (4000, stats(4, 22.0, 4.0, 7.0)),
+        (8000, stats(2, 17.0, 8.0, 9.0))
+      ]
+    );
+
+    let buckets = table
+      .fold_buckets(.., 5000, timestamp_of, |_| Vec::new(), |keys, key, _| keys.push(*key))
+      .unwrap();
+    assert_eq!(
+      buckets,
+      vec![(0, vec![1, 2, 3, 4]), (5000, vec![5, 6, 7, 8, 9]), (10000, vec![10])]
+    );
+    assert!(table.aggregate(11.., 4000, timestamp_of, project).unwrap().is_empty());
+    assert!(matches!(
+      table.aggregate(.., 0, timestamp_of, project),
+      Err(Error::InvalidBucketWidth(0))
+    ));
+  }
+
   #[test]
   fn test_get_first_key() {
     setup!("table_enhanced.get_first_key"; db);
@@ -451,6 +758,48 @@ mod tests {
     assert_eq!(table.get_last_key().unwrap(), k3);
   }
 
+  #[test]
+  fn test_put_with_expiry() {
+    setup_with_ttl!("table_enhanced.put_with_expiry"; 60; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap().enhance::<Key, Value, Coder>();
+
+    let v1 = Bytes::from("1");
+    let v2 = Bytes::from("2");
+    let v3 = Bytes::from("3");
+
+    table.put_with_ttl(1, &v1, 60).unwrap();
+    table.put_with_expiry(2, &v2, now() - 1000).unwrap();
+    table.put(3, &v3).unwrap();
+
+    db.inner.compact_range(None::<&[u8]>, None::<&[u8]>);
+
+    assert_eq!(table.get(1).unwrap().unwrap(), v1);
+    assert!(table.get_including_expired(2).unwrap().is_none());
+    assert_eq!(table.get(3).unwrap().unwrap(), v3);
+  }
+
+  #[test]
+  fn test_get_including_expired() {
+    setup_with_ttl!("table_enhanced.get_including_expired"; 60; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap().enhance::<Key, Value, Coder>();
+
+    let v1 = Bytes::from("1");
+    let v2 = Bytes::from("2");
+
+    table.put_with_expiry(1, &v1, now() - 1000).unwrap();
+    table.put(2, &v2).unwrap();
+
+    assert!(table.get(1).unwrap().is_none());
+    assert_eq!(table.get_including_expired(1).unwrap().unwrap(), v1);
+    assert_eq!(table.get_first_key().unwrap(), 2);
+
+    let mut cursor = table.new_cursor_including_expired();
+    cursor.seek_to_first();
+    assert_eq!(cursor.key().unwrap(), 1);
+  }
+
   #[test]
   fn test_get_boundary_keys() {
     setup!("table_enhanced.get_boundary_keys"; db);
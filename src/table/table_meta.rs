@@ -0,0 +1,34 @@
+use std::collections::BTreeMap;
+
+use prost::Message;
+
+/// Metadata kept in the catalog for each table. Times are in milliseconds. `modified_at` is
+/// updated whenever the table is renamed or truncated, or its metadata is set.
+#[derive(Clone, PartialEq, Message)]
+pub struct TableMeta {
+  #[prost(uint64, tag = "1")]
+  pub created_at: u64,
+  #[prost(uint64, tag = "2")]
+  pub modified_at: u64,
+  #[prost(string, tag = "3")]
+  pub description: String,
+  /// The type name of the coder the table is meant to be enhanced with, if any.
+  #[prost(string, tag = "4")]
+  pub coder: String,
+  #[prost(btree_map = "string, string", tag = "5")]
+  pub properties: BTreeMap<String, String>,
+}
+
+impl TableMeta {
+  #[inline]
+  pub fn set_coder<C: ?Sized>(&mut self) {
+    self.coder = std::any::type_name::<C>().to_string();
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableInfo {
+  pub name: String,
+  pub id: u32,
+  pub meta: TableMeta,
+}
@@ -5,10 +5,14 @@ use bytes::{Buf, Bytes};
 use rocksdb::ReadOptions;
 use rocksdb::DB as RocksdbDb;
 
+use super::live_tables::Liveness;
 use super::table::Table;
-use crate::consts::*;
+use crate::column_family::ColumnFamily;
 use crate::cursor::*;
 use crate::error::Error;
+use crate::floor_key_index::FloorKeyChanges;
+use crate::rollup::RollupRegistry;
+use crate::ttl_registry::{ttl_to_millis, TtlRegistry};
 use crate::types::*;
 use crate::utils::*;
 use crate::write_batch::*;
@@ -18,6 +22,10 @@ pub struct TtlTable {
   pub(crate) inner_db: Arc<RocksdbDb>,
   pub(crate) id: TableId,
   pub(crate) tail_anchor: Bytes,
+  pub(crate) cf: ColumnFamily,
+  pub(crate) ttl_registry: Arc<TtlRegistry>,
+  pub(crate) liveness: Arc<Liveness>,
+  pub(crate) rollups: Arc<RollupRegistry>,
 }
 
 impl fmt::Debug for TtlTable {
@@ -35,71 +43,196 @@ impl Table for TtlTable {
     self.id
   }
 
+  #[inline]
+  fn group(&self) -> &str {
+    self.cf.group()
+  }
+
+  #[inline]
+  fn is_alive(&self) -> bool {
+    self.liveness.is_alive()
+  }
+
   #[inline]
   fn put<K, V>(&self, key: K, value: V) -> Result<(), Error>
   where
     K: AsRef<[u8]>,
     V: AsRef<[u8]>, {
-    Ok(
-      self
-        .inner_db
-        .put(build_inner_key(self.id, key), build_timestamped_value(u32_to_u8a4(now()), value))?,
-    )
+    if self.rollups.is_source(self.id) {
+      let mut batch = self.new_write_batch();
+      batch.put(key, value);
+      return batch.write();
+    }
+    let _alive = self.liveness.ensure_alive(self.id)?;
+    let key = key.as_ref();
+    let value = self.ttl_registry.build_value(self.id, key, value);
+    self.cf.put(&self.inner_db, build_inner_key(self.id, key), value)?;
+    self.ttl_registry.on_put(self.id, key);
+    Ok(())
   }
 
   #[inline]
   fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<(), Error> {
-    Ok(self.inner_db.delete(build_inner_key(self.id, key))?)
+    if self.rollups.is_source(self.id) {
+      let mut batch = self.new_write_batch();
+      batch.delete(key);
+      return batch.write();
+    }
+    let _alive = self.liveness.ensure_alive(self.id)?;
+    let key = key.as_ref();
+    let mut changes = FloorKeyChanges::default();
+    changes.delete_key(self.id, key);
+    self.ttl_registry.track(&self.inner_db, changes, || {
+      self.cf.delete(&self.inner_db, build_inner_key(self.id, key))
+    })
   }
 
   #[inline]
   fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Bytes>, Error> {
-    if let Some(timestamped_value) = self.inner_db.get(build_inner_key(self.id, key))? {
-      let mut value = Bytes::from(timestamped_value);
-      value.advance(TIMESTAMP_LEN);
-      Ok(Some(value))
-    } else {
-      Ok(None)
-    }
+    self.get_opt(key, true)
   }
 
   #[inline]
   fn new_write_batch(&self) -> Self::WriteBatch {
-    TtlWriteBatch::new(self.inner_db.clone(), self.id)
+    TtlWriteBatch::with_liveness(
+      self.inner_db.clone(),
+      self.id,
+      self.cf.clone(),
+      self.ttl_registry.clone(),
+      self.liveness.clone(),
+      self.rollups.clone(),
+    )
   }
 
   #[inline]
   fn new_cursor<'a>(&'a self) -> Self::Cursor<'a> {
-    let mut opts = ReadOptions::default();
-    opts.set_prefix_same_as_start(true);
-    TtlCursor::new(self.inner_db.raw_iterator_opt(opts), self.id, &self.tail_anchor)
+    self.new_cursor_opt(true)
   }
 }
 
 impl TtlTable {
   #[inline]
-  pub(crate) fn new(inner_db: Arc<RocksdbDb>, id: TableId) -> Self {
-    TtlTable { inner_db, id, tail_anchor: build_tail_anchor(id) }
+  pub(crate) fn new(
+    inner_db: Arc<RocksdbDb>, id: TableId, cf: ColumnFamily, ttl_registry: Arc<TtlRegistry>,
+    liveness: Arc<Liveness>, rollups: Arc<RollupRegistry>,
+  ) -> Self {
+    let tail_anchor = build_tail_anchor(id);
+    TtlTable { inner_db, id, tail_anchor, cf, ttl_registry, liveness, rollups }
   }
 
+  /// Same as `get`, but also returns a value that has expired and not been compacted away yet.
   #[inline]
-  pub fn put_timestamped<K, V>(&self, key: K, value: V) -> Result<u32, Error>
+  pub fn get_including_expired<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Bytes>, Error> {
+    self.get_opt(key, false)
+  }
+
+  /// Same as `new_cursor`, but the cursor also visits the values that have expired and not been
+  /// compacted away yet.
+  #[inline]
+  pub fn new_cursor_including_expired<'a>(&'a self) -> TtlCursor<'a> {
+    self.new_cursor_opt(false)
+  }
+
+  #[inline]
+  pub fn put_timestamped<K, V>(&self, key: K, value: V) -> Result<u64, Error>
   where
     K: AsRef<[u8]>,
     V: AsRef<[u8]>, {
+    if self.rollups.is_source(self.id) {
+      let mut batch = self.new_write_batch();
+      let now = batch.put_timestamped(key, value);
+      return batch.write().map(|_| now);
+    }
+    let _alive = self.liveness.ensure_alive(self.id)?;
+    let key = key.as_ref();
     let now = now();
-    self
-      .inner_db
-      .put(build_inner_key(self.id, key), build_timestamped_value(u32_to_u8a4(now), value))?;
+    self.cf.put(
+      &self.inner_db,
+      build_inner_key(self.id, key),
+      build_timestamped_value(u64_to_u8a8(now), value),
+    )?;
+    self.ttl_registry.on_put(self.id, key);
     Ok(now)
   }
+
+  /// Puts a value which expires at `expires_at_millis`, a unix time in milliseconds, instead of
+  /// after the table's ttl.
+  #[inline]
+  pub fn put_with_expiry<K, V>(
+    &self, key: K, value: V, expires_at_millis: u64,
+  ) -> Result<(), Error>
+  where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>, {
+    if self.rollups.is_source(self.id) {
+      let mut batch = self.new_write_batch();
+      batch.put_with_expiry(key, value, expires_at_millis);
+      return batch.write();
+    }
+    let _alive = self.liveness.ensure_alive(self.id)?;
+    let key = key.as_ref();
+    self.cf.put(
+      &self.inner_db,
+      build_inner_key(self.id, key),
+      build_expiring_value(u64_to_u8a8(now()), u64_to_u8a8(expires_at_millis), value),
+    )?;
+    self.ttl_registry.on_put(self.id, key);
+    Ok(())
+  }
+
+  /// Puts a value which expires `ttl_secs` seconds from now instead of after the table's ttl.
+  #[inline]
+  pub fn put_with_ttl<K, V>(&self, key: K, value: V, ttl_secs: u32) -> Result<(), Error>
+  where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>, {
+    self.put_with_expiry(key, value, now().saturating_add(ttl_to_millis(ttl_secs)))
+  }
+
+  #[inline]
+  fn get_opt<K: AsRef<[u8]>>(&self, key: K, hide_expired: bool) -> Result<Option<Bytes>, Error> {
+    let _alive = self.liveness.ensure_alive(self.id)?;
+    let key = key.as_ref();
+    let inner_key = build_inner_key(self.id, key);
+    if let Some(timestamped_value) = self.cf.get(&self.inner_db, &inner_key)? {
+      let header_len = extract_header_len(&timestamped_value)
+        .ok_or_else(|| Error::CorruptedValue(inner_key.to_vec()))?;
+      if hide_expired && ExpiryFilter::new(self).is_expired(key, &timestamped_value) {
+        return Ok(None);
+      }
+      let mut value = Bytes::from(timestamped_value);
+      value.advance(header_len);
+      Ok(Some(value))
+    } else {
+      Ok(None)
+    }
+  }
+
+  #[inline]
+  fn new_cursor_opt(&self, hide_expired: bool) -> TtlCursor<'_> {
+    let mut opts = ReadOptions::default();
+    opts.set_prefix_same_as_start(true);
+    let mut cursor = TtlCursor::with_expiry_filter(
+      self.cf.raw_iterator_opt(&self.inner_db, opts),
+      self.id,
+      &self.tail_anchor,
+      ExpiryFilter::new(self),
+      hide_expired,
+    );
+    // The id of a destroyed table may already be reused by another one.
+    cursor.table_destroyed = !self.liveness.is_alive();
+    cursor
+  }
 }
 
 #[cfg(test)]
 mod tests {
+  use crate::cursor::*;
   use crate::db::*;
+  use crate::error::Error;
   use crate::setup_with_ttl;
   use crate::table::*;
+  use crate::utils::*;
 
   #[test]
   fn test_put() {
@@ -123,6 +256,75 @@ mod tests {
     assert_eq!(std::str::from_utf8(&result.unwrap().unwrap()).unwrap(), "v111");
   }
 
+  #[test]
+  fn test_put_with_expiry() {
+    setup_with_ttl!("ttl_table.test_put_with_expiry"; 3; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap();
+    assert!(table.put_with_expiry(b"k111", b"v111", now() + 60_000).is_ok());
+    assert!(table.put_with_ttl(b"k222", b"v222", 60).is_ok());
+    assert_eq!(std::str::from_utf8(&table.get(b"k111").unwrap().unwrap()).unwrap(), "v111");
+    assert_eq!(std::str::from_utf8(&table.get(b"k222").unwrap().unwrap()).unwrap(), "v222");
+    assert!(table.put_with_expiry(b"k333", b"v333", now() - 1000).is_ok());
+    assert!(table.get(b"k333").unwrap().is_none());
+  }
+
+  #[test]
+  fn test_get_expired() {
+    setup_with_ttl!("ttl_table.test_get_expired"; 60; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap();
+    table.put_with_expiry(b"k111", b"v111", now() - 1000).unwrap();
+    table.put_with_ttl(b"k222", b"v222", 60).unwrap();
+    table.put(b"k333", b"v333").unwrap();
+
+    assert!(table.get(b"k111").unwrap().is_none());
+    assert_eq!(std::str::from_utf8(&table.get(b"k222").unwrap().unwrap()).unwrap(), "v222");
+    assert_eq!(std::str::from_utf8(&table.get(b"k333").unwrap().unwrap()).unwrap(), "v333");
+    let result = table.get_including_expired(b"k111");
+    assert_eq!(std::str::from_utf8(&result.unwrap().unwrap()).unwrap(), "v111");
+  }
+
+  #[test]
+  fn test_get_secs_header() {
+    setup_with_ttl!("ttl_table.test_get_secs_header"; 3; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap();
+    let secs = (now() / 1000) as u32;
+    let value = [&[0][..], &u32_to_u8a4(secs)[..], b"v111"].concat();
+    db.inner.put(build_inner_key(table.id(), b"k111"), value).unwrap();
+    let value = [&[1][..], &u32_to_u8a4(secs)[..], &u32_to_u8a4(secs - 1)[..], b"v222"].concat();
+    db.inner.put(build_inner_key(table.id(), b"k222"), value).unwrap();
+    table.put(b"k333", b"v333").unwrap();
+
+    assert_eq!(std::str::from_utf8(&table.get(b"k111").unwrap().unwrap()).unwrap(), "v111");
+    assert!(table.get(b"k222").unwrap().is_none());
+    let mut cursor = table.new_cursor();
+    cursor.seek_to_first();
+    assert_eq!(cursor.timestamped_value().unwrap(), (secs as u64 * 1000, &b"v111"[..]));
+  }
+
+  #[test]
+  fn test_get_corrupted_value() {
+    setup_with_ttl!("ttl_table.test_get_corrupted_value"; 3; db);
+    let name = "huobi.btc.usdt.1min";
+    let table = db.open_table(name).unwrap();
+    table.put(b"k111", b"v111").unwrap();
+    db.inner.put(build_inner_key(table.id(), b"k222"), []).unwrap();
+    db.inner.put(build_inner_key(table.id(), b"k333"), [3, 0, 0]).unwrap();
+
+    assert!(matches!(table.get(b"k222"), Err(Error::CorruptedValue(_))));
+    assert!(matches!(table.get(b"k333"), Err(Error::CorruptedValue(_))));
+    let mut cursor = table.new_cursor();
+    cursor.seek_to_first();
+    assert_eq!(cursor.value().unwrap(), b"v111");
+    cursor.next();
+    assert!(!cursor.is_valid());
+    assert!(matches!(cursor.status(), Err(Error::CorruptedValue(_))));
+    db.inner.compact_range(None::<&[u8]>, None::<&[u8]>);
+    assert_eq!(db.inner.get(build_inner_key(table.id(), b"k333")).unwrap().unwrap(), [3, 0, 0]);
+  }
+
   #[allow(unused_must_use)]
   #[test]
   fn test_delete() {
@@ -0,0 +1,273 @@
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc, RwLock,
+};
+
+use ahash::AHashMap;
+use bytes::Bytes;
+use rocksdb::{WriteBatch as RocksdbWriteBatch, DB as RocksdbDb};
+
+use crate::column_family::Routing;
+use crate::consts::*;
+use crate::cursor::*;
+use crate::error::Error;
+use crate::floor_key_index::*;
+use crate::key_timestamp_extractor::KeyTimestampExtractor;
+use crate::table::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// The ttls in effect for a ttl db: the db-wide default plus per-table overrides
+/// persisted in the `ID_TO_TTL_TABLE_ID` system table. The number of newest records kept
+/// regardless of the ttl is overridden the same way in `ID_TO_KEEP_LATEST_TABLE_ID`. Also
+/// holds the key timestamp extractors, which live in memory only, and the floor key index.
+pub(crate) struct TtlRegistry {
+  ttl: u32,
+  table_ttls: RwLock<AHashMap<TableId, u32>>,
+  keep_latest: u32,
+  table_keep_latests: RwLock<AHashMap<TableId, u32>>,
+  key_timestamp_extractor: RwLock<Option<Arc<dyn KeyTimestampExtractor>>>,
+  table_key_timestamp_extractors: RwLock<AHashMap<TableId, Arc<dyn KeyTimestampExtractor>>>,
+  floor_key_index: FloorKeyIndex,
+  ready: AtomicBool,
+}
+
+impl TtlRegistry {
+  #[inline]
+  pub(crate) fn new(ttl: u32, keep_latest: u32) -> Self {
+    TtlRegistry {
+      ttl,
+      table_ttls: RwLock::new(AHashMap::new()),
+      keep_latest,
+      table_keep_latests: RwLock::new(AHashMap::new()),
+      key_timestamp_extractor: RwLock::new(None),
+      table_key_timestamp_extractors: RwLock::new(AHashMap::new()),
+      floor_key_index: FloorKeyIndex::new(),
+      ready: AtomicBool::new(false),
+    }
+  }
+
+  // Compactions must not expire anything until the db has been upgraded and the table ttls
+  // have been loaded.
+  #[inline]
+  pub(crate) fn is_ready(&self) -> bool {
+    self.ready.load(Ordering::Acquire)
+  }
+
+  #[inline]
+  pub(crate) fn set_ready(&self) {
+    self.ready.store(true, Ordering::Release)
+  }
+
+  #[inline(always)]
+  pub(crate) fn ttl(&self) -> u32 {
+    self.ttl
+  }
+
+  #[inline]
+  pub(crate) fn get_ttl(&self, table_id: TableId) -> u32 {
+    self.get_table_ttl(table_id).unwrap_or(self.ttl)
+  }
+
+  #[inline]
+  pub(crate) fn get_table_ttl(&self, table_id: TableId) -> Option<u32> {
+    self.table_ttls.read().unwrap().get(&table_id).copied()
+  }
+
+  #[inline]
+  pub(crate) fn set_table_ttl(&self, table_id: TableId, ttl: u32) {
+    self.table_ttls.write().unwrap().insert(table_id, ttl);
+  }
+
+  #[inline]
+  pub(crate) fn remove_table_ttl(&self, table_id: TableId) {
+    self.table_ttls.write().unwrap().remove(&table_id);
+  }
+
+  #[inline(always)]
+  pub(crate) fn keep_latest(&self) -> u32 {
+    self.keep_latest
+  }
+
+  #[inline]
+  pub(crate) fn get_keep_latest(&self, table_id: TableId) -> u32 {
+    self.get_table_keep_latest(table_id).unwrap_or(self.keep_latest)
+  }
+
+  #[inline]
+  pub(crate) fn get_table_keep_latest(&self, table_id: TableId) -> Option<u32> {
+    self.table_keep_latests.read().unwrap().get(&table_id).copied()
+  }
+
+  #[inline]
+  pub(crate) fn set_table_keep_latest(&self, table_id: TableId, keep_latest: u32) {
+    self.table_keep_latests.write().unwrap().insert(table_id, keep_latest);
+  }
+
+  #[inline]
+  pub(crate) fn remove_table_keep_latest(&self, table_id: TableId) {
+    self.table_keep_latests.write().unwrap().remove(&table_id);
+  }
+
+  #[inline]
+  pub(crate) fn get_key_timestamp_extractor(
+    &self, table_id: TableId,
+  ) -> Option<Arc<dyn KeyTimestampExtractor>> {
+    if let Some(extractor) = self.table_key_timestamp_extractors.read().unwrap().get(&table_id) {
+      return Some(extractor.clone());
+    }
+    self.key_timestamp_extractor.read().unwrap().clone()
+  }
+
+  #[inline]
+  pub(crate) fn set_key_timestamp_extractor(&self, extractor: Arc<dyn KeyTimestampExtractor>) {
+    *self.key_timestamp_extractor.write().unwrap() = Some(extractor);
+  }
+
+  #[inline]
+  pub(crate) fn set_table_key_timestamp_extractor(
+    &self, table_id: TableId, extractor: Arc<dyn KeyTimestampExtractor>,
+  ) {
+    self.table_key_timestamp_extractors.write().unwrap().insert(table_id, extractor);
+  }
+
+  #[inline]
+  pub(crate) fn remove_table_key_timestamp_extractor(&self, table_id: TableId) {
+    self.table_key_timestamp_extractors.write().unwrap().remove(&table_id);
+  }
+
+  #[inline]
+  pub(crate) fn get_expiry_rule(&self, table_id: TableId) -> ExpiryRule {
+    ExpiryRule {
+      ttl: self.get_ttl(table_id),
+      key_timestamp_extractor: self.get_key_timestamp_extractor(table_id),
+    }
+  }
+
+  // Values of keys which carry a timestamp are stored without one when an extractor is registered.
+  #[inline]
+  pub(crate) fn build_value<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+    &self, table_id: TableId, key: K, value: V,
+  ) -> Bytes {
+    match self.get_key_timestamp_extractor(table_id) {
+      Some(extractor) if extractor.extract(key.as_ref()).is_some() => {
+        build_key_timestamped_value(value)
+      }
+      _ => build_timestamped_value(u64_to_u8a8(now()), value),
+    }
+  }
+
+  #[inline]
+  pub(crate) fn get_floor(&self, table_id: TableId) -> Floor {
+    self.floor_key_index.get_floor(table_id, self.get_keep_latest(table_id))
+  }
+
+  #[inline]
+  pub(crate) fn floor_key_generation(&self) -> u64 {
+    self.floor_key_index.generation()
+  }
+
+  #[inline]
+  pub(crate) fn on_put(&self, table_id: TableId, key: &[u8]) {
+    self.floor_key_index.on_put(table_id, key, self.get_keep_latest(table_id))
+  }
+
+  /// Routes and writes the batch, keeping the floor key index up to date.
+  #[inline]
+  pub(crate) fn write(
+    &self, inner_db: &Arc<RocksdbDb>, batch: RocksdbWriteBatch, routing: &Routing,
+  ) -> Result<(), Error> {
+    let mut changes = FloorKeyChanges::default();
+    batch.iterate(&mut changes);
+    let batch = routing.apply(inner_db, batch)?;
+    self.track(inner_db, changes, || inner_db.write(batch))
+  }
+
+  #[inline]
+  pub(crate) fn track<F: FnOnce() -> Result<(), rocksdb::Error>>(
+    &self, inner_db: &Arc<RocksdbDb>, changes: FloorKeyChanges, write: F,
+  ) -> Result<(), Error> {
+    self.floor_key_index.track(inner_db, changes, |id| self.get_keep_latest(id), write)
+  }
+
+  #[inline]
+  pub(crate) fn refresh_floor(
+    &self, inner_db: &Arc<RocksdbDb>, table_id: TableId,
+  ) -> Result<(), Error> {
+    self.floor_key_index.refresh(inner_db, table_id, self.get_keep_latest(table_id))
+  }
+
+  #[inline]
+  pub(crate) fn remove_floor(&self, table_id: TableId) {
+    self.floor_key_index.remove(table_id)
+  }
+
+  pub(crate) fn load(&self, inner_db: Arc<RocksdbDb>) -> Result<(), Error> {
+    Self::load_overrides(inner_db.clone(), ID_TO_TTL_TABLE_ID, &self.table_ttls)?;
+    Self::load_overrides(inner_db.clone(), ID_TO_KEEP_LATEST_TABLE_ID, &self.table_keep_latests)?;
+    self.load_floors(inner_db)
+  }
+
+  fn load_floors(&self, inner_db: Arc<RocksdbDb>) -> Result<(), Error> {
+    let id_to_name_table = NormalTable::new(inner_db.clone(), ID_TO_NAME_TABLE_ID);
+    let mut cursor = id_to_name_table.new_cursor();
+    cursor.seek_to_first();
+    while cursor.is_valid() {
+      self.refresh_floor(&inner_db, u8s_to_u8a4(cursor.key().unwrap()))?;
+      cursor.next();
+    }
+    cursor.status()
+  }
+
+  fn load_overrides(
+    inner_db: Arc<RocksdbDb>, system_table_id: TableId, overrides: &RwLock<AHashMap<TableId, u32>>,
+  ) -> Result<(), Error> {
+    let system_table = NormalTable::new(inner_db, system_table_id);
+    let mut cursor = system_table.new_cursor();
+    cursor.seek_to_first();
+    let mut overrides = overrides.write().unwrap();
+    while cursor.is_valid() {
+      overrides.insert(u8s_to_u8a4(cursor.key().unwrap()), u8s_to_u32(cursor.value().unwrap()));
+      cursor.next();
+    }
+    cursor.status()
+  }
+}
+
+/// How the values of a table expire: a value carrying its own expiry expires then, any other
+/// value expires ttl seconds after its timestamp. Values whose timestamp is derived from the key
+/// never expire while no extractor is registered for their table. Whether the newest keys are
+/// protected is up to their `Floor`.
+#[derive(Clone, Default)]
+pub(crate) struct ExpiryRule {
+  ttl: u32,
+  key_timestamp_extractor: Option<Arc<dyn KeyTimestampExtractor>>,
+}
+
+impl ExpiryRule {
+  #[inline]
+  pub(crate) fn get_timestamp(&self, key: &[u8], value: &[u8]) -> Option<u64> {
+    if value.first() == Some(&KEY_TIMESTAMPED_HEADER_KIND) {
+      self.key_timestamp_extractor.as_ref().and_then(|extractor| extractor.extract(key))
+    } else {
+      extract_timestamp(value)
+    }
+  }
+
+  /// The result is in milliseconds.
+  #[inline]
+  pub(crate) fn get_expires_at(&self, key: &[u8], value: &[u8]) -> u64 {
+    if let Some(expires_at) = extract_expires_at(value) {
+      return expires_at;
+    }
+    match self.get_timestamp(key, value) {
+      Some(timestamp) => timestamp.saturating_add(ttl_to_millis(self.ttl)),
+      None => u64::MAX,
+    }
+  }
+}
+
+#[inline]
+pub(crate) fn ttl_to_millis(ttl: u32) -> u64 {
+  ttl as u64 * 1000
+}
@@ -4,6 +4,8 @@ pub type U8a4 = [u8; 4];
 
 pub type TableId = U8a4;
 
-pub type Timestamp = U8a4;
+pub type U8a8 = [u8; 8];
+
+pub type Timestamp = U8a8;
 
 pub(crate) type ItemId = [u8; 2];
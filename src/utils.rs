@@ -32,6 +32,18 @@ pub fn u8s_to_u32(u8s: &[u8]) -> u32 {
   BigEndian::read_u32(u8s)
 }
 
+#[inline]
+pub fn u64_to_u8a8(u64: u64) -> U8a8 {
+  let mut buf = [0; 8];
+  BigEndian::write_u64(&mut buf, u64);
+  buf
+}
+
+#[inline]
+pub fn u8s_to_u64(u8s: &[u8]) -> u64 {
+  BigEndian::read_u64(u8s)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 /// key and value utils
 ////////////////////////////////////////////////////////////////////////////////
@@ -50,6 +62,41 @@ pub fn build_id_to_name_table_inner_key(table_id: TableId) -> Bytes {
   build_inner_key(ID_TO_NAME_TABLE_ID, table_id)
 }
 
+#[inline]
+pub fn build_id_to_ttl_table_inner_key(table_id: TableId) -> Bytes {
+  build_inner_key(ID_TO_TTL_TABLE_ID, table_id)
+}
+
+#[inline]
+pub fn build_id_to_keep_latest_table_inner_key(table_id: TableId) -> Bytes {
+  build_inner_key(ID_TO_KEEP_LATEST_TABLE_ID, table_id)
+}
+
+#[inline]
+pub fn build_id_to_retention_table_inner_key(table_id: TableId) -> Bytes {
+  build_inner_key(ID_TO_RETENTION_TABLE_ID, table_id)
+}
+
+#[inline]
+pub fn build_id_to_meta_table_inner_key(table_id: TableId) -> Bytes {
+  build_inner_key(ID_TO_META_TABLE_ID, table_id)
+}
+
+#[inline]
+pub fn build_free_id_table_inner_key(table_id: TableId) -> Bytes {
+  build_inner_key(FREE_ID_TABLE_ID, table_id)
+}
+
+#[inline]
+pub fn build_id_to_group_table_inner_key(table_id: TableId) -> Bytes {
+  build_inner_key(ID_TO_GROUP_TABLE_ID, table_id)
+}
+
+#[inline]
+pub fn build_alias_to_id_table_inner_key<A: AsRef<[u8]>>(alias: A) -> Bytes {
+  build_inner_key(ALIAS_TO_ID_TABLE_ID, alias)
+}
+
 #[inline]
 pub fn build_inner_key<K: AsRef<[u8]>>(table_id: TableId, key: K) -> Bytes {
   let key = key.as_ref();
@@ -94,12 +141,45 @@ pub fn build_tail_anchor(table_id: TableId) -> Bytes {
 #[inline]
 pub fn build_timestamped_value<V: AsRef<[u8]>>(timestamp: Timestamp, value: V) -> Bytes {
   let value = value.as_ref();
-  let len = TIMESTAMP_LEN + value.len();
+  let len = TIMESTAMPED_HEADER_LEN + value.len();
+  let mut buf = BytesMut::with_capacity(len);
+  unsafe {
+    let dst = slice::from_raw_parts_mut(buf.as_mut_ptr(), len);
+    dst[0] = TIMESTAMPED_HEADER_KIND;
+    copy_nonoverlapping(timestamp.as_ref(), dst, HEADER_KIND_LEN);
+    copy_nonoverlapping(value, dst, TIMESTAMPED_HEADER_LEN);
+    buf.set_len(len);
+  }
+  buf.freeze()
+}
+
+#[inline]
+pub fn build_expiring_value<V: AsRef<[u8]>>(
+  timestamp: Timestamp, expires_at: Timestamp, value: V,
+) -> Bytes {
+  let value = value.as_ref();
+  let len = EXPIRING_HEADER_LEN + value.len();
+  let mut buf = BytesMut::with_capacity(len);
+  unsafe {
+    let dst = slice::from_raw_parts_mut(buf.as_mut_ptr(), len);
+    dst[0] = EXPIRING_HEADER_KIND;
+    copy_nonoverlapping(timestamp.as_ref(), dst, HEADER_KIND_LEN);
+    copy_nonoverlapping(expires_at.as_ref(), dst, HEADER_KIND_LEN + TIMESTAMP_LEN);
+    copy_nonoverlapping(value, dst, EXPIRING_HEADER_LEN);
+    buf.set_len(len);
+  }
+  buf.freeze()
+}
+
+#[inline]
+pub fn build_key_timestamped_value<V: AsRef<[u8]>>(value: V) -> Bytes {
+  let value = value.as_ref();
+  let len = KEY_TIMESTAMPED_HEADER_LEN + value.len();
   let mut buf = BytesMut::with_capacity(len);
   unsafe {
     let dst = slice::from_raw_parts_mut(buf.as_mut_ptr(), len);
-    copy_nonoverlapping(timestamp.as_ref(), dst, 0);
-    copy_nonoverlapping(value, dst, TIMESTAMP_LEN);
+    dst[0] = KEY_TIMESTAMPED_HEADER_KIND;
+    copy_nonoverlapping(value, dst, KEY_TIMESTAMPED_HEADER_LEN);
     buf.set_len(len);
   }
   buf.freeze()
@@ -115,22 +195,90 @@ pub fn extract_key<'a>(buf: &'a [u8]) -> &'a [u8] {
   &buf[TABLE_ID_LEN + 1..]
 }
 
+/// Returns `None` if the value is corrupted, i.e. empty, of an unknown kind or shorter than its
+/// header.
+#[inline]
+pub fn extract_header_len(buf: &[u8]) -> Option<usize> {
+  let header_len = match *buf.first()? {
+    SECS_TIMESTAMPED_HEADER_KIND => SECS_TIMESTAMPED_HEADER_LEN,
+    SECS_EXPIRING_HEADER_KIND => SECS_EXPIRING_HEADER_LEN,
+    EXPIRING_HEADER_KIND => EXPIRING_HEADER_LEN,
+    TIMESTAMPED_HEADER_KIND => TIMESTAMPED_HEADER_LEN,
+    KEY_TIMESTAMPED_HEADER_KIND => KEY_TIMESTAMPED_HEADER_LEN,
+    _ => return None,
+  };
+  (buf.len() >= header_len).then_some(header_len)
+}
+
+/// Returns the timestamp in milliseconds, whichever unit the header was written with, or `None`
+/// if the timestamp is derived from the key or the value is corrupted.
+#[inline]
+pub fn extract_timestamp(buf: &[u8]) -> Option<u64> {
+  extract_header_len(buf)?;
+  match buf[0] {
+    SECS_TIMESTAMPED_HEADER_KIND | SECS_EXPIRING_HEADER_KIND => {
+      Some(u8s_to_u32(&buf[HEADER_KIND_LEN..]) as u64 * 1000)
+    }
+    KEY_TIMESTAMPED_HEADER_KIND => None,
+    _ => Some(u8s_to_u64(&buf[HEADER_KIND_LEN..])),
+  }
+}
+
+/// Returns the expiry in milliseconds if the header carries one.
 #[inline]
-pub fn extract_timestamp(buf: &[u8]) -> &[u8] {
-  &buf[..TIMESTAMP_LEN]
+pub fn extract_expires_at(buf: &[u8]) -> Option<u64> {
+  extract_header_len(buf)?;
+  match buf[0] {
+    SECS_EXPIRING_HEADER_KIND => {
+      Some(u8s_to_u32(&buf[HEADER_KIND_LEN + SECS_TIMESTAMP_LEN..]) as u64 * 1000)
+    }
+    EXPIRING_HEADER_KIND => Some(u8s_to_u64(&buf[HEADER_KIND_LEN + TIMESTAMP_LEN..])),
+    _ => None,
+  }
 }
 
+/// Returns `None` if the value is corrupted.
 #[inline]
-pub fn extract_value(buf: &[u8]) -> &[u8] {
-  &buf[TIMESTAMP_LEN..]
+pub fn extract_value(buf: &[u8]) -> Option<&[u8]> {
+  Some(&buf[extract_header_len(buf)?..])
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 /// other utils
 ////////////////////////////////////////////////////////////////////////////////
+/// Returns the current time in milliseconds.
 #[inline]
-pub fn now() -> u32 {
-  Utc::now().timestamp() as u32
+pub fn now() -> u64 {
+  Utc::now().timestamp_millis() as u64
+}
+
+/// Matches a table name against a pattern in which `*` stands for any run of characters and `?`
+/// for any single one.
+pub fn matches_glob(pattern: &str, name: &str) -> bool {
+  let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+  let (mut p, mut n) = (0, 0);
+  let mut backtrack: Option<(usize, usize)> = None;
+  while n < name.len() {
+    match pattern.get(p) {
+      Some(b'*') => {
+        backtrack = Some((p, n));
+        p += 1;
+      }
+      Some(&c) if c == b'?' || c == name[n] => {
+        p += 1;
+        n += 1;
+      }
+      _ => match backtrack {
+        Some((star_p, star_n)) => {
+          backtrack = Some((star_p, star_n + 1));
+          p = star_p + 1;
+          n = star_n + 1;
+        }
+        None => return false,
+      },
+    }
+  }
+  pattern[p..].iter().all(|&c| c == b'*')
 }
 
 #[inline]
@@ -174,9 +322,13 @@ pub(crate) mod test_utils {
 
   impl TestContext<NormalDb> {
     pub fn new(db_name: &str) -> Self {
+      Self::with_opts(db_name, &mut crate::options::Options::new())
+    }
+
+    pub fn with_opts(db_name: &str, opts: &mut crate::options::Options) -> Self {
       let mut path = String::from("./data/");
       path.push_str(db_name);
-      let result = NormalDb::open(path.clone(), &mut crate::options::Options::new());
+      let result = NormalDb::open(path.clone(), opts);
       assert!(result.is_ok());
       TestContext { db: Some(Arc::new(result.unwrap())), path: path }
     }
@@ -184,9 +336,13 @@ pub(crate) mod test_utils {
 
   impl TestContext<TtlDb> {
     pub fn with_ttl(db_name: &str, ttl: u32) -> Self {
+      Self::with_ttl_and_opts(db_name, ttl, &mut crate::options::Options::new())
+    }
+
+    pub fn with_ttl_and_opts(db_name: &str, ttl: u32, opts: &mut crate::options::Options) -> Self {
       let mut path = String::from("./data/");
       path.push_str(db_name);
-      let result = TtlDb::open(path.clone(), ttl, &mut crate::options::Options::new());
+      let result = TtlDb::open(path.clone(), ttl, opts);
       assert!(result.is_ok());
       TestContext { db: Some(Arc::new(result.unwrap())), path: path }
     }
@@ -249,6 +405,19 @@ mod tests {
     assert_eq!(build_id_to_name_table_inner_key([0, 0, 4, 0]), vec![0, 0, 0, 2, 1, 0, 0, 4, 0]);
   }
 
+  #[test]
+  fn test_build_id_to_ttl_table_inner_key() {
+    assert_eq!(build_id_to_ttl_table_inner_key([0, 0, 4, 0]), vec![0, 0, 0, 3, 1, 0, 0, 4, 0]);
+  }
+
+  #[test]
+  fn test_build_id_to_keep_latest_table_inner_key() {
+    assert_eq!(
+      build_id_to_keep_latest_table_inner_key([0, 0, 4, 0]),
+      vec![0, 0, 0, 4, 1, 0, 0, 4, 0]
+    );
+  }
+
   #[test]
   fn test_build_head_anchor() {
     assert_eq!(build_head_anchor([0, 0, 4, 0]), vec![0, 0, 4, 0, 0]);
@@ -272,10 +441,70 @@ mod tests {
     assert_eq!(table_id, [0, 0, 4, 0]);
   }
 
+  #[test]
+  fn test_build_timestamped_value() {
+    let timestamped_value = build_timestamped_value(u64_to_u8a8(9), b"v");
+    assert_eq!(timestamped_value, vec![2, 0, 0, 0, 0, 0, 0, 0, 9, 118]);
+    assert_eq!(extract_timestamp(&timestamped_value), Some(9));
+    assert!(extract_expires_at(&timestamped_value).is_none());
+    assert_eq!(extract_value(&timestamped_value).unwrap(), b"v");
+  }
+
+  #[test]
+  fn test_build_expiring_value() {
+    let expiring_value = build_expiring_value(u64_to_u8a8(9), u64_to_u8a8(256), b"v");
+    assert_eq!(expiring_value, vec![3, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 1, 0, 118]);
+    assert_eq!(extract_timestamp(&expiring_value), Some(9));
+    assert_eq!(extract_expires_at(&expiring_value).unwrap(), 256);
+    assert_eq!(extract_value(&expiring_value).unwrap(), b"v");
+  }
+
+  #[test]
+  fn test_build_key_timestamped_value() {
+    let key_timestamped_value = build_key_timestamped_value(b"v");
+    assert_eq!(key_timestamped_value, vec![4, 118]);
+    assert!(extract_timestamp(&key_timestamped_value).is_none());
+    assert!(extract_expires_at(&key_timestamped_value).is_none());
+    assert_eq!(extract_value(&key_timestamped_value).unwrap(), b"v");
+  }
+
+  #[test]
+  fn test_extract_secs_header() {
+    let timestamped_value = [0, 0, 0, 0, 9, 118];
+    assert_eq!(extract_timestamp(&timestamped_value), Some(9000));
+    assert!(extract_expires_at(&timestamped_value).is_none());
+    assert_eq!(extract_value(&timestamped_value).unwrap(), b"v");
+
+    let expiring_value = [1, 0, 0, 0, 9, 0, 0, 1, 0, 118];
+    assert_eq!(extract_timestamp(&expiring_value), Some(9000));
+    assert_eq!(extract_expires_at(&expiring_value).unwrap(), 256000);
+    assert_eq!(extract_value(&expiring_value).unwrap(), b"v");
+  }
+
+  #[test]
+  fn test_extract_corrupted_header() {
+    assert!(extract_header_len(&[]).is_none());
+    assert!(extract_value(&[3, 0, 0]).is_none());
+    assert!(extract_timestamp(&[2, 0]).is_none());
+    assert!(extract_expires_at(&[1, 0, 0, 0, 9]).is_none());
+    assert_eq!(extract_header_len(&[4]), Some(1));
+    assert!(extract_header_len(&[9, 0, 0, 0, 0, 0, 0, 0, 0, 1]).is_none());
+    assert!(extract_timestamp(&[9, 0, 0, 0, 0, 0, 0, 0, 0, 1]).is_none());
+  }
+
   #[test]
   fn test_extract_key() {
     let inner_key = [0, 0, 4, 0, 1, 0, 0, 0, 128, 0, 254];
     let key = extract_key(&inner_key);
     assert_eq!(key, [0, 0, 0, 128, 0, 254]);
   }
+
+  #[test]
+  fn test_matches_glob() {
+    assert!(matches_glob("huobi.*.1min", "huobi.btc.usdt.1min"));
+    assert!(matches_glob("huobi.???.*", "huobi.btc.usdt.1min"));
+    assert!(matches_glob("*", ""));
+    assert!(!matches_glob("huobi.*.1min", "huobi.btc.usdt.1m"));
+    assert!(!matches_glob("huobi.?", "huobi."));
+  }
 }
@@ -4,7 +4,10 @@ use rocksdb::WriteBatch as RocksdbWriteBatch;
 use rocksdb::DB as RocksdbDb;
 
 use super::write_batch::*;
+use crate::column_family::*;
 use crate::error::Error;
+use crate::rollup::RollupRegistry;
+use crate::table::Liveness;
 use crate::types::*;
 use crate::utils::*;
 
@@ -12,6 +15,9 @@ pub struct NormalWriteBatch {
   pub(crate) inner_db: Arc<RocksdbDb>,
   pub(crate) inner: Option<RocksdbWriteBatch>,
   pub(crate) table_id: TableId,
+  pub(crate) routing: Routing,
+  pub(crate) liveness: Arc<Liveness>,
+  pub(crate) rollups: Arc<RollupRegistry>,
 }
 
 impl WriteBatch for NormalWriteBatch {
@@ -42,14 +48,29 @@ impl WriteBatch for NormalWriteBatch {
 
   #[inline]
   fn write(mut self) -> Result<(), Error> {
-    Ok(self.inner_db.write(self.inner.take().unwrap())?)
+    let _alive = self.liveness.ensure_alive(self.table_id)?;
+    let batch = self.inner.take().unwrap();
+    self.rollups.write(&self.inner_db, batch, &self.routing, |batch, routing| {
+      Ok(self.inner_db.write(routing.apply(&self.inner_db, batch)?)?)
+    })
   }
 }
 
 impl NormalWriteBatch {
   #[inline]
   pub fn new(inner_db: Arc<RocksdbDb>, table_id: TableId) -> Self {
-    NormalWriteBatch { inner_db, inner: Some(RocksdbWriteBatch::default()), table_id }
+    let cf = ColumnFamily::default();
+    Self::with_liveness(inner_db, table_id, cf, Arc::default(), Arc::default())
+  }
+
+  #[inline]
+  pub(crate) fn with_liveness(
+    inner_db: Arc<RocksdbDb>, table_id: TableId, cf: ColumnFamily, liveness: Arc<Liveness>,
+    rollups: Arc<RollupRegistry>,
+  ) -> Self {
+    let inner = Some(RocksdbWriteBatch::default());
+    let routing = Routing::To(cf);
+    NormalWriteBatch { inner_db, inner, table_id, routing, liveness, rollups }
   }
 }
 
@@ -4,13 +4,17 @@ use rocksdb::WriteBatch as RocksdbWriteBatch;
 use rocksdb::DB as RocksdbDb;
 
 use super::write_batch_x::*;
+use crate::column_family::Routing;
 use crate::error::Error;
+use crate::rollup::RollupRegistry;
 use crate::types::*;
 use crate::utils::*;
 
 pub struct NormalWriteBatchX {
   pub(crate) inner_db: Arc<RocksdbDb>,
   pub(crate) inner: Option<RocksdbWriteBatch>,
+  pub(crate) routing: Routing,
+  pub(crate) rollups: Arc<RollupRegistry>,
 }
 
 impl WriteBatchX for NormalWriteBatchX {
@@ -35,14 +39,19 @@ impl WriteBatchX for NormalWriteBatchX {
 
   #[inline]
   fn write(mut self) -> Result<(), Error> {
-    Ok(self.inner_db.write(self.inner.take().unwrap())?)
+    let batch = self.inner.take().unwrap();
+    self.rollups.write(&self.inner_db, batch, &self.routing, |batch, routing| {
+      Ok(self.inner_db.write(routing.apply(&self.inner_db, batch)?)?)
+    })
   }
 }
 
 impl NormalWriteBatchX {
   #[inline]
-  pub(crate) fn new(inner_db: Arc<RocksdbDb>) -> Self {
-    NormalWriteBatchX { inner_db, inner: Some(RocksdbWriteBatch::default()) }
+  pub(crate) fn new(
+    inner_db: Arc<RocksdbDb>, routing: Routing, rollups: Arc<RollupRegistry>,
+  ) -> Self {
+    NormalWriteBatchX { inner_db, inner: Some(RocksdbWriteBatch::default()), routing, rollups }
   }
 }
 
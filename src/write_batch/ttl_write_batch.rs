@@ -4,7 +4,11 @@ use rocksdb::WriteBatch as RocksdbWriteBatch;
 use rocksdb::DB as RocksdbDb;
 
 use super::write_batch::*;
+use crate::column_family::*;
 use crate::error::Error;
+use crate::rollup::RollupRegistry;
+use crate::table::Liveness;
+use crate::ttl_registry::{ttl_to_millis, TtlRegistry};
 use crate::types::*;
 use crate::utils::*;
 
@@ -12,6 +16,10 @@ pub struct TtlWriteBatch {
   pub(crate) inner_db: Arc<RocksdbDb>,
   pub(crate) inner: Option<RocksdbWriteBatch>,
   pub(crate) table_id: TableId,
+  pub(crate) routing: Routing,
+  pub(crate) ttl_registry: Arc<TtlRegistry>,
+  pub(crate) liveness: Arc<Liveness>,
+  pub(crate) rollups: Arc<RollupRegistry>,
 }
 
 impl WriteBatch for TtlWriteBatch {
@@ -37,25 +45,43 @@ impl WriteBatch for TtlWriteBatch {
     K: AsRef<[u8]>,
     V: AsRef<[u8]>, {
     let table_id = self.table_id();
-    self
-      .inner_mut()
-      .put(build_inner_key(table_id, key), build_timestamped_value(u32_to_u8a4(now()), value))
+    let key = key.as_ref();
+    let value = self.ttl_registry.build_value(table_id, key, value);
+    self.inner_mut().put(build_inner_key(table_id, key), value)
   }
 
   #[inline]
   fn write(mut self) -> Result<(), Error> {
-    Ok(self.inner_db.write(self.inner.take().unwrap())?)
+    let _alive = self.liveness.ensure_alive(self.table_id)?;
+    let batch = self.inner.take().unwrap();
+    self.rollups.write(&self.inner_db, batch, &self.routing, |batch, routing| {
+      self.ttl_registry.write(&self.inner_db, batch, routing)
+    })
   }
 }
 
 impl TtlWriteBatch {
+  /// Builds a batch for a table of the default group which knows of no per-table settings, i.e.
+  /// key timestamp extractors. Prefer `TtlTable::new_write_batch`.
   #[inline]
   pub fn new(inner_db: Arc<RocksdbDb>, table_id: TableId) -> Self {
-    TtlWriteBatch { inner_db, inner: Some(RocksdbWriteBatch::default()), table_id }
+    let ttl_registry = Arc::new(TtlRegistry::new(0, 0));
+    let cf = ColumnFamily::default();
+    Self::with_liveness(inner_db, table_id, cf, ttl_registry, Arc::default(), Arc::default())
+  }
+
+  #[inline]
+  pub(crate) fn with_liveness(
+    inner_db: Arc<RocksdbDb>, table_id: TableId, cf: ColumnFamily, ttl_registry: Arc<TtlRegistry>,
+    liveness: Arc<Liveness>, rollups: Arc<RollupRegistry>,
+  ) -> Self {
+    let inner = Some(RocksdbWriteBatch::default());
+    let routing = Routing::To(cf);
+    TtlWriteBatch { inner_db, inner, table_id, routing, ttl_registry, liveness, rollups }
   }
 
   #[inline]
-  pub fn put_timestamped<K, V>(&mut self, key: K, value: V) -> u32
+  pub fn put_timestamped<K, V>(&mut self, key: K, value: V) -> u64
   where
     K: AsRef<[u8]>,
     V: AsRef<[u8]>, {
@@ -63,9 +89,32 @@ impl TtlWriteBatch {
     let table_id = self.table_id();
     self
       .inner_mut()
-      .put(build_inner_key(table_id, key), build_timestamped_value(u32_to_u8a4(now), value));
+      .put(build_inner_key(table_id, key), build_timestamped_value(u64_to_u8a8(now), value));
     now
   }
+
+  /// Puts a value which expires at `expires_at_millis`, a unix time in milliseconds, instead of
+  /// after the table's ttl.
+  #[inline]
+  pub fn put_with_expiry<K, V>(&mut self, key: K, value: V, expires_at_millis: u64)
+  where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>, {
+    let table_id = self.table_id();
+    self.inner_mut().put(
+      build_inner_key(table_id, key),
+      build_expiring_value(u64_to_u8a8(now()), u64_to_u8a8(expires_at_millis), value),
+    )
+  }
+
+  /// Puts a value which expires `ttl_secs` seconds from now instead of after the table's ttl.
+  #[inline]
+  pub fn put_with_ttl<K, V>(&mut self, key: K, value: V, ttl_secs: u32)
+  where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>, {
+    self.put_with_expiry(key, value, now().saturating_add(ttl_to_millis(ttl_secs)))
+  }
 }
 
 #[cfg(test)]
@@ -90,12 +139,16 @@ mod tests {
     wb.put(b"k4", b"v4");
     let ts = wb.put_timestamped(b"k5", b"v5");
     assert!(ts > 0);
+    wb.put_with_expiry(b"k6", b"v6", ts + 60_000);
+    wb.put_with_ttl(b"k7", b"v7", 60);
     wb.delete(b"k2");
     wb.delete_range(b"k3", b"k5");
     assert!(wb.write().is_ok());
 
     assert_eq!(table.get(b"k1").unwrap().unwrap().as_ref(), b"v1");
     assert_eq!(table.get(b"k5").unwrap().unwrap().as_ref(), b"v5");
+    assert_eq!(table.get(b"k6").unwrap().unwrap().as_ref(), b"v6");
+    assert_eq!(table.get(b"k7").unwrap().unwrap().as_ref(), b"v7");
 
     assert!(table.get(b"k2").unwrap().is_none());
     assert!(table.get(b"k3").unwrap().is_none());
@@ -4,13 +4,19 @@ use rocksdb::WriteBatch as RocksdbWriteBatch;
 use rocksdb::DB as RocksdbDb;
 
 use super::write_batch_x::*;
+use crate::column_family::Routing;
 use crate::error::Error;
+use crate::rollup::RollupRegistry;
+use crate::ttl_registry::{ttl_to_millis, TtlRegistry};
 use crate::types::*;
 use crate::utils::*;
 
 pub struct TtlWriteBatchX {
   pub(crate) inner_db: Arc<RocksdbDb>,
   pub(crate) inner: Option<RocksdbWriteBatch>,
+  pub(crate) routing: Routing,
+  pub(crate) ttl_registry: Arc<TtlRegistry>,
+  pub(crate) rollups: Arc<RollupRegistry>,
 }
 
 impl WriteBatchX for TtlWriteBatchX {
@@ -30,34 +36,72 @@ impl WriteBatchX for TtlWriteBatchX {
   where
     K: AsRef<[u8]>,
     V: AsRef<[u8]>, {
-    self
-      .inner_mut()
-      .put(build_inner_key(table_id, key), build_timestamped_value(u32_to_u8a4(now()), value))
+    let key = key.as_ref();
+    let value = self.ttl_registry.build_value(table_id, key, value);
+    self.inner_mut().put(build_inner_key(table_id, key), value)
   }
 
   #[inline]
   fn write(mut self) -> Result<(), Error> {
-    Ok(self.inner_db.write(self.inner.take().unwrap())?)
+    let batch = self.inner.take().unwrap();
+    self.rollups.write(&self.inner_db, batch, &self.routing, |batch, routing| {
+      self.ttl_registry.write(&self.inner_db, batch, routing)
+    })
   }
 }
 
 impl TtlWriteBatchX {
+  /// Builds a batch which knows of no per-table settings, i.e. key timestamp extractors. Prefer
+  /// `TtlDb::new_write_batch_x`.
+  #[inline]
+  pub fn new(inner_db: Arc<RocksdbDb>) -> Self {
+    let ttl_registry = Arc::new(TtlRegistry::new(0, 0));
+    Self::with_routing(inner_db, Routing::ByTable, ttl_registry, Arc::default())
+  }
+
   #[inline]
-  pub(crate) fn new(inner_db: Arc<RocksdbDb>) -> Self {
-    TtlWriteBatchX { inner_db, inner: Some(RocksdbWriteBatch::default()) }
+  pub(crate) fn with_routing(
+    inner_db: Arc<RocksdbDb>, routing: Routing, ttl_registry: Arc<TtlRegistry>,
+    rollups: Arc<RollupRegistry>,
+  ) -> Self {
+    let inner = Some(RocksdbWriteBatch::default());
+    TtlWriteBatchX { inner_db, inner, routing, ttl_registry, rollups }
   }
 
   #[inline]
-  pub fn put_timestamped<K, V>(&mut self, table_id: TableId, key: K, value: V) -> u32
+  pub fn put_timestamped<K, V>(&mut self, table_id: TableId, key: K, value: V) -> u64
   where
     K: AsRef<[u8]>,
     V: AsRef<[u8]>, {
     let now = now();
     self
       .inner_mut()
-      .put(build_inner_key(table_id, key), build_timestamped_value(u32_to_u8a4(now), value));
+      .put(build_inner_key(table_id, key), build_timestamped_value(u64_to_u8a8(now), value));
     now
   }
+
+  /// Puts a value which expires at `expires_at_millis`, a unix time in milliseconds, instead of
+  /// after the table's ttl.
+  #[inline]
+  pub fn put_with_expiry<K, V>(
+    &mut self, table_id: TableId, key: K, value: V, expires_at_millis: u64,
+  ) where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>, {
+    self.inner_mut().put(
+      build_inner_key(table_id, key),
+      build_expiring_value(u64_to_u8a8(now()), u64_to_u8a8(expires_at_millis), value),
+    )
+  }
+
+  /// Puts a value which expires `ttl_secs` seconds from now instead of after the table's ttl.
+  #[inline]
+  pub fn put_with_ttl<K, V>(&mut self, table_id: TableId, key: K, value: V, ttl_secs: u32)
+  where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>, {
+    self.put_with_expiry(table_id, key, value, now().saturating_add(ttl_to_millis(ttl_secs)))
+  }
 }
 
 #[cfg(test)]
@@ -84,6 +128,8 @@ mod tests {
     wb.put(table3m.id(), b"k4", b"v4");
     let ts = wb.put_timestamped(table3m.id(), b"k5", b"v5");
     assert!(ts > 0);
+    wb.put_with_expiry(table3m.id(), b"k6", b"v6", ts + 60_000);
+    wb.put_with_ttl(table3m.id(), b"k7", b"v7", 60);
 
     wb.delete(table1m.id(), b"k2");
     wb.delete_range(table3m.id(), b"k3", b"k5");
@@ -95,5 +141,7 @@ mod tests {
     assert!(table3m.get(b"k3").unwrap().is_none());
     assert!(table3m.get(b"k4").unwrap().is_none());
     assert_eq!(table3m.get(b"k5").unwrap().unwrap().as_ref(), b"v5");
+    assert_eq!(table3m.get(b"k6").unwrap().unwrap().as_ref(), b"v6");
+    assert_eq!(table3m.get(b"k7").unwrap().unwrap().as_ref(), b"v7");
   }
 }
@@ -36,6 +36,23 @@ impl<WB: WriteBatch, K, V, C: Coder<K, V>> WriteBatchEnhanced<WB, K, V, C> {
   }
 }
 
+impl<K, V, C: Coder<K, V>> WriteBatchEnhanced<TtlWriteBatch, K, V, C> {
+  /// Puts a value which expires at `expires_at_millis`, a unix time in milliseconds, instead of
+  /// after the table's ttl.
+  #[inline]
+  pub fn put_with_expiry<BK: Borrow<K>, BV: Borrow<V>>(
+    &mut self, key: BK, value: BV, expires_at_millis: u64,
+  ) {
+    self.raw.put_with_expiry(C::encode_key(key), C::encode_value(value), expires_at_millis)
+  }
+
+  /// Puts a value which expires `ttl_secs` seconds from now instead of after the table's ttl.
+  #[inline]
+  pub fn put_with_ttl<BK: Borrow<K>, BV: Borrow<V>>(&mut self, key: BK, value: BV, ttl_secs: u32) {
+    self.raw.put_with_ttl(C::encode_key(key), C::encode_value(value), ttl_secs)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use std::borrow::Borrow;
@@ -121,12 +138,14 @@ mod tests {
     wb.put(3, 3);
     wb.put(4, 4);
     wb.put(5, 5);
+    wb.put_with_ttl(6, 6, 60);
     wb.delete(2);
     wb.delete_range(3, 5);
     assert!(wb.write().is_ok());
 
     assert_eq!(table.get(1).unwrap().unwrap(), 1);
     assert_eq!(table.get(5).unwrap().unwrap(), 5);
+    assert_eq!(table.get(6).unwrap().unwrap(), 6);
 
     assert!(table.get(2).unwrap().is_none());
     assert!(table.get(3).unwrap().is_none());
@@ -37,6 +37,30 @@ impl<WB: WriteBatchX, K, V, C: Coder<K, V>> WriteBatchXEnhanced<WB, K, V, C> {
   }
 }
 
+impl<K, V, C: Coder<K, V>> WriteBatchXEnhanced<TtlWriteBatchX, K, V, C> {
+  /// Puts a value which expires at `expires_at_millis`, a unix time in milliseconds, instead of
+  /// after the table's ttl.
+  #[inline]
+  pub fn put_with_expiry<BK: Borrow<K>, BV: Borrow<V>>(
+    &mut self, table_id: TableId, key: BK, value: BV, expires_at_millis: u64,
+  ) {
+    self.raw.put_with_expiry(
+      table_id,
+      C::encode_key(key),
+      C::encode_value(value),
+      expires_at_millis,
+    )
+  }
+
+  /// Puts a value which expires `ttl_secs` seconds from now instead of after the table's ttl.
+  #[inline]
+  pub fn put_with_ttl<BK: Borrow<K>, BV: Borrow<V>>(
+    &mut self, table_id: TableId, key: BK, value: BV, ttl_secs: u32,
+  ) {
+    self.raw.put_with_ttl(table_id, C::encode_key(key), C::encode_value(value), ttl_secs)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use std::borrow::Borrow;
@@ -127,12 +151,14 @@ mod tests {
     wb.put(table1m.id(), 3, 3);
     wb.put(table3m.id(), 4, 4);
     wb.put(table3m.id(), 5, 5);
+    wb.put_with_ttl(table3m.id(), 6, 6, 60);
     wb.delete(table1m.id(), 2);
     wb.delete_range(table3m.id(), 3, 5);
     assert!(wb.write().is_ok());
 
     assert_eq!(table1m.get(1).unwrap().unwrap(), 1);
     assert_eq!(table1m.get(3).unwrap().unwrap(), 3);
+    assert_eq!(table3m.get(6).unwrap().unwrap(), 6);
 
     assert!(table3m.get(3).unwrap().is_none());
     assert!(table3m.get(4).unwrap().is_none());
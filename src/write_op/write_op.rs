@@ -47,4 +47,8 @@ pub enum WriteOp {
 pub struct OptionalWriteOp {
   #[prost(oneof = "WriteOp", tags = "1, 2, 3, 4")]
   pub inner: Option<WriteOp>,
+  /// The id of the column family written to, which is 0 for the default group. Ids are local to
+  /// the db, see `Db::replay`.
+  #[prost(uint32, tag = "5")]
+  pub cf_id: u32,
 }
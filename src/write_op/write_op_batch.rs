@@ -1,6 +1,6 @@
 use bytes::Bytes;
 use prost::Message;
-use rocksdb::WriteBatchIterator;
+use rocksdb::WriteBatchIteratorCf;
 
 use super::write_op::*;
 
@@ -12,38 +12,40 @@ pub struct WriteOpBatch {
   pub write_ops: Vec<OptionalWriteOp>,
 }
 
-impl WriteBatchIterator for WriteOpBatch {
+impl WriteBatchIteratorCf for WriteOpBatch {
   #[inline]
-  fn put(&mut self, inner_key: Box<[u8]>, inner_value: Box<[u8]>) {
+  fn put_cf(&mut self, cf_id: u32, inner_key: Box<[u8]>, inner_value: Box<[u8]>) {
     let put_op = PutOp {
       inner_key: Bytes::copy_from_slice(inner_key.as_ref()),
       inner_value: Bytes::copy_from_slice(inner_value.as_ref()),
     };
-    self.write_ops.push(OptionalWriteOp { inner: Some(WriteOp::PutOp(put_op)) })
+    self.write_ops.push(OptionalWriteOp { inner: Some(WriteOp::PutOp(put_op)), cf_id })
   }
 
   #[inline]
-  fn delete(&mut self, inner_key: Box<[u8]>) {
+  fn delete_cf(&mut self, cf_id: u32, inner_key: Box<[u8]>) {
     let delete_op = DeleteOp { inner_key: Bytes::copy_from_slice(inner_key.as_ref()) };
-    self.write_ops.push(OptionalWriteOp { inner: Some(WriteOp::DeleteOp(delete_op)) })
+    self.write_ops.push(OptionalWriteOp { inner: Some(WriteOp::DeleteOp(delete_op)), cf_id })
   }
 
   #[inline]
-  fn delete_range(&mut self, begin_inner_key: Box<[u8]>, end_inner_key: Box<[u8]>) {
+  fn delete_range_cf(&mut self, cf_id: u32, begin_inner_key: Box<[u8]>, end_inner_key: Box<[u8]>) {
     let delete_range_op = DeleteRangeOp {
       begin_inner_key: Bytes::copy_from_slice(begin_inner_key.as_ref()),
       end_inner_key: Bytes::copy_from_slice(end_inner_key.as_ref()),
     };
-    self.write_ops.push(OptionalWriteOp { inner: Some(WriteOp::DeleteRangeOp(delete_range_op)) })
+    self
+      .write_ops
+      .push(OptionalWriteOp { inner: Some(WriteOp::DeleteRangeOp(delete_range_op)), cf_id })
   }
 
   #[inline]
-  fn merge(&mut self, inner_key: Box<[u8]>, inner_value: Box<[u8]>) {
+  fn merge_cf(&mut self, cf_id: u32, inner_key: Box<[u8]>, inner_value: Box<[u8]>) {
     let merge_op = MergeOp {
       inner_key: Bytes::copy_from_slice(inner_key.as_ref()),
       inner_value: Bytes::copy_from_slice(inner_value.as_ref()),
     };
-    self.write_ops.push(OptionalWriteOp { inner: Some(WriteOp::MergeOp(merge_op)) })
+    self.write_ops.push(OptionalWriteOp { inner: Some(WriteOp::MergeOp(merge_op)), cf_id })
   }
 }
 
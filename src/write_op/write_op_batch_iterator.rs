@@ -17,7 +17,7 @@ impl Iterator for WriteOpBatchIterator {
           Ok((sn, batch_inner)) => {
             let mut batch = WriteOpBatch::new();
             batch.sn = sn;
-            batch_inner.iterate(&mut batch);
+            batch_inner.iterate_cf(&mut batch);
             return Some(Ok(batch));
           }
           Err(err) => {